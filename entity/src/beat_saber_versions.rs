@@ -10,14 +10,37 @@ pub struct Model {
     pub id: Uuid,
     #[sea_orm(unique)]
     pub ver: String,
+    pub release_date: Option<Date>,
+    pub deprecated: bool,
+    pub is_latest: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::beat_saber_version_aliases::Entity")]
+    BeatSaberVersionAliases,
+    #[sea_orm(has_many = "super::compatibility_reports::Entity")]
+    CompatibilityReports,
     #[sea_orm(has_many = "super::mod_beat_saber_versions::Entity")]
     ModBeatSaberVersions,
     #[sea_orm(has_many = "super::version_beat_saber_versions::Entity")]
     VersionBeatSaberVersions,
+    #[sea_orm(has_many = "super::version_download_rollups::Entity")]
+    VersionDownloadRollups,
+    #[sea_orm(has_many = "super::version_downloads::Entity")]
+    VersionDownloads,
+}
+
+impl Related<super::beat_saber_version_aliases::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BeatSaberVersionAliases.def()
+    }
+}
+
+impl Related<super::compatibility_reports::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CompatibilityReports.def()
+    }
 }
 
 impl Related<super::mod_beat_saber_versions::Entity> for Entity {
@@ -32,4 +55,16 @@ impl Related<super::version_beat_saber_versions::Entity> for Entity {
     }
 }
 
+impl Related<super::version_download_rollups::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VersionDownloadRollups.def()
+    }
+}
+
+impl Related<super::version_downloads::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VersionDownloads.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -12,14 +12,23 @@ pub struct Model {
     pub name: String,
     #[sea_orm(unique)]
     pub description: String,
+    pub retired: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::mod_categories::Entity")]
+    ModCategories,
     #[sea_orm(has_many = "super::mods::Entity")]
     Mods,
 }
 
+impl Related<super::mod_categories::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModCategories.def()
+    }
+}
+
 impl Related<super::mods::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Mods.def()
@@ -2,16 +2,26 @@
 
 pub mod prelude;
 
+pub mod beat_saber_version_aliases;
 pub mod beat_saber_versions;
 pub mod categories;
+pub mod compatibility_reports;
+pub mod jobs;
 pub mod mod_beat_saber_versions;
+pub mod mod_categories;
+pub mod mod_media;
+pub mod mod_ratings;
 pub mod mod_stats;
+pub mod mod_tags;
 pub mod mod_versions;
 pub mod mods;
+pub mod user_identities;
 pub mod user_mods;
 pub mod users;
 pub mod version_beat_saber_versions;
 pub mod version_conflicts;
 pub mod version_dependents;
+pub mod version_download_rollups;
+pub mod version_downloads;
 pub mod version_stats;
 pub mod versions;
@@ -3,12 +3,17 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "mod_stats")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
     pub downloads: i32,
+    #[sea_orm(column_type = "Double")]
+    pub rating: f64,
+    pub rating_count: i32,
+    #[sea_orm(column_type = "Double")]
+    pub trending_score: f64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
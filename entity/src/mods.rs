@@ -21,6 +21,11 @@ pub struct Model {
     pub stats: Uuid,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    pub mod_type: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub long_description: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub long_description_html: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -35,6 +40,12 @@ pub enum Relation {
     Categories,
     #[sea_orm(has_many = "super::mod_beat_saber_versions::Entity")]
     ModBeatSaberVersions,
+    #[sea_orm(has_many = "super::mod_categories::Entity")]
+    ModCategories,
+    #[sea_orm(has_many = "super::mod_media::Entity")]
+    ModMedia,
+    #[sea_orm(has_many = "super::mod_ratings::Entity")]
+    ModRatings,
     #[sea_orm(
         belongs_to = "super::mod_stats::Entity",
         from = "Column::Stats",
@@ -43,6 +54,8 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     ModStats,
+    #[sea_orm(has_many = "super::mod_tags::Entity")]
+    ModTags,
     #[sea_orm(has_many = "super::mod_versions::Entity")]
     ModVersions,
     #[sea_orm(has_many = "super::user_mods::Entity")]
@@ -71,12 +84,36 @@ impl Related<super::mod_beat_saber_versions::Entity> for Entity {
     }
 }
 
+impl Related<super::mod_categories::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModCategories.def()
+    }
+}
+
+impl Related<super::mod_media::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModMedia.def()
+    }
+}
+
+impl Related<super::mod_ratings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModRatings.def()
+    }
+}
+
 impl Related<super::mod_stats::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ModStats.def()
     }
 }
 
+impl Related<super::mod_tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModTags.def()
+    }
+}
+
 impl Related<super::mod_versions::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ModVersions.def()
@@ -1,15 +1,25 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
 
+pub use super::beat_saber_version_aliases::Entity as BeatSaberVersionAliases;
 pub use super::beat_saber_versions::Entity as BeatSaberVersions;
 pub use super::categories::Entity as Categories;
+pub use super::compatibility_reports::Entity as CompatibilityReports;
+pub use super::jobs::Entity as Jobs;
 pub use super::mod_beat_saber_versions::Entity as ModBeatSaberVersions;
+pub use super::mod_categories::Entity as ModCategories;
+pub use super::mod_media::Entity as ModMedia;
+pub use super::mod_ratings::Entity as ModRatings;
 pub use super::mod_stats::Entity as ModStats;
+pub use super::mod_tags::Entity as ModTags;
 pub use super::mod_versions::Entity as ModVersions;
 pub use super::mods::Entity as Mods;
+pub use super::user_identities::Entity as UserIdentities;
 pub use super::user_mods::Entity as UserMods;
 pub use super::users::Entity as Users;
 pub use super::version_beat_saber_versions::Entity as VersionBeatSaberVersions;
 pub use super::version_conflicts::Entity as VersionConflicts;
 pub use super::version_dependents::Entity as VersionDependents;
+pub use super::version_download_rollups::Entity as VersionDownloadRollups;
+pub use super::version_downloads::Entity as VersionDownloads;
 pub use super::version_stats::Entity as VersionStats;
 pub use super::versions::Entity as Versions;
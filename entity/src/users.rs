@@ -9,7 +9,7 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
     #[sea_orm(unique)]
-    pub github_id: i32,
+    pub github_id: Option<i64>,
     #[sea_orm(unique)]
     pub username: String,
     pub display_name: Option<String>,
@@ -23,22 +23,49 @@ pub struct Model {
     pub api_key: Uuid,
     pub created_at: DateTime,
     pub updated_at: DateTime,
+    pub last_login_at: Option<DateTime>,
+    pub profile_overrides: i32,
+    pub upload_limit: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::compatibility_reports::Entity")]
+    CompatibilityReports,
+    #[sea_orm(has_many = "super::mod_ratings::Entity")]
+    ModRatings,
     #[sea_orm(has_many = "super::mods::Entity")]
     Mods,
+    #[sea_orm(has_many = "super::user_identities::Entity")]
+    UserIdentities,
     #[sea_orm(has_many = "super::user_mods::Entity")]
     UserMods,
 }
 
+impl Related<super::compatibility_reports::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CompatibilityReports.def()
+    }
+}
+
+impl Related<super::mod_ratings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModRatings.def()
+    }
+}
+
 impl Related<super::mods::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Mods.def()
     }
 }
 
+impl Related<super::user_identities::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentities.def()
+    }
+}
+
 impl Related<super::user_mods::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserMods.def()
@@ -17,10 +17,19 @@ pub struct Model {
     pub artifact_hash: String,
     pub download_url: String,
     pub created_at: DateTime,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub analysis: Option<Json>,
+    pub risky: bool,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub release_notes: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::compatibility_reports::Entity")]
+    CompatibilityReports,
+    #[sea_orm(has_many = "super::mod_ratings::Entity")]
+    ModRatings,
     #[sea_orm(has_many = "super::mod_versions::Entity")]
     ModVersions,
     #[sea_orm(
@@ -33,6 +42,10 @@ pub enum Relation {
     Mods,
     #[sea_orm(has_many = "super::version_beat_saber_versions::Entity")]
     VersionBeatSaberVersions,
+    #[sea_orm(has_many = "super::version_download_rollups::Entity")]
+    VersionDownloadRollups,
+    #[sea_orm(has_many = "super::version_downloads::Entity")]
+    VersionDownloads,
     #[sea_orm(
         belongs_to = "super::version_stats::Entity",
         from = "Column::Stats",
@@ -43,6 +56,18 @@ pub enum Relation {
     VersionStats,
 }
 
+impl Related<super::compatibility_reports::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CompatibilityReports.def()
+    }
+}
+
+impl Related<super::mod_ratings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModRatings.def()
+    }
+}
+
 impl Related<super::mod_versions::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ModVersions.def()
@@ -61,6 +86,18 @@ impl Related<super::version_beat_saber_versions::Entity> for Entity {
     }
 }
 
+impl Related<super::version_download_rollups::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VersionDownloadRollups.def()
+    }
+}
+
+impl Related<super::version_downloads::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VersionDownloads.def()
+    }
+}
+
 impl Related<super::version_stats::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::VersionStats.def()
@@ -3,4 +3,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize)]
 pub struct Entity {
     pub downloads: u64,
+    #[serde(default)]
+    pub rating: f64,
+    #[serde(default)]
+    pub rating_count: u64,
+    #[serde(default)]
+    pub trending_score: f64,
 }
\ No newline at end of file
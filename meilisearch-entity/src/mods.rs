@@ -5,13 +5,23 @@ use serde::{Deserialize, Serialize};
 pub struct Entity {
     pub id: sea_orm::prelude::Uuid,
     pub slug: String,
+    #[serde(default = "default_mod_type")]
+    pub mod_type: String,
     pub name: String,
     pub description: String,
     pub versions: Vec<super::version::Entity>,
     pub category: String,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub author: super::user::Entity,
     pub stats: super::mod_stats::Entity,
     pub supported_versions: Vec<Version>,
     pub created_at: i64,
     pub updated_at: i64
-}
\ No newline at end of file
+}
+
+fn default_mod_type() -> String {
+    "mod".to_string()
+}
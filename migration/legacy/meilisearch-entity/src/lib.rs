@@ -0,0 +1,5 @@
+pub mod prelude;
+pub mod mods;
+pub mod user;
+pub mod version;
+pub mod mod_stats;
\ No newline at end of file
@@ -0,0 +1,6 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Entity {
+    pub downloads: u64,
+}
\ No newline at end of file
@@ -0,0 +1,4 @@
+pub use super::mods::Entity as MeiliMod;
+pub use super::user::Entity as MeiliUser;
+pub use super::version::Entity as MeiliVersion;
+pub use super::mod_stats::Entity as MeiliModStats;
\ No newline at end of file
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Entity {
+    pub username: String,
+    pub display_name: String,
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Entity {
+    pub version: Version,
+}
\ No newline at end of file
@@ -5,6 +5,27 @@ mod m20230727_015400_load_default_data;
 mod m20230727_063415_create_mod_beat_saber_versions;
 mod m20230806_034429_melilisearch;
 mod m20230813_235044_reclean_download_urls;
+mod m20261019_000001_beat_saber_version_management;
+mod m20261019_000002_category_management;
+mod m20261019_000003_user_login_sync;
+mod m20261019_000004_user_identities;
+mod m20261019_000005_widen_github_id;
+mod m20261019_000006_user_upload_limit;
+mod m20261019_000007_jobs;
+mod m20261019_000008_version_analysis;
+mod m20261019_000009_mod_type;
+mod m20261019_000010_compatibility_reports;
+mod m20261019_000011_mod_ratings;
+mod m20261019_000012_release_notes;
+mod m20261019_000013_long_description;
+mod m20261019_000014_mod_media;
+mod m20261019_000015_version_downloads;
+mod m20261019_000016_trending_score;
+mod m20261019_000017_default_edit_mod;
+mod m20261019_000018_reindex_mods;
+mod m20261019_000019_download_rollups;
+mod m20261019_000020_unique_artifact_hash;
+mod m20261019_000021_long_description_html;
 
 pub struct Migrator;
 
@@ -17,6 +38,27 @@ impl MigratorTrait for Migrator {
             Box::new(m20230727_063415_create_mod_beat_saber_versions::Migration),
             Box::new(m20230806_034429_melilisearch::Migration),
             Box::new(m20230813_235044_reclean_download_urls::Migration),
+            Box::new(m20261019_000001_beat_saber_version_management::Migration),
+            Box::new(m20261019_000002_category_management::Migration),
+            Box::new(m20261019_000003_user_login_sync::Migration),
+            Box::new(m20261019_000004_user_identities::Migration),
+            Box::new(m20261019_000005_widen_github_id::Migration),
+            Box::new(m20261019_000006_user_upload_limit::Migration),
+            Box::new(m20261019_000007_jobs::Migration),
+            Box::new(m20261019_000008_version_analysis::Migration),
+            Box::new(m20261019_000009_mod_type::Migration),
+            Box::new(m20261019_000010_compatibility_reports::Migration),
+            Box::new(m20261019_000011_mod_ratings::Migration),
+            Box::new(m20261019_000012_release_notes::Migration),
+            Box::new(m20261019_000013_long_description::Migration),
+            Box::new(m20261019_000014_mod_media::Migration),
+            Box::new(m20261019_000015_version_downloads::Migration),
+            Box::new(m20261019_000016_trending_score::Migration),
+            Box::new(m20261019_000017_default_edit_mod::Migration),
+            Box::new(m20261019_000018_reindex_mods::Migration),
+            Box::new(m20261019_000019_download_rollups::Migration),
+            Box::new(m20261019_000020_unique_artifact_hash::Migration),
+            Box::new(m20261019_000021_long_description_html::Migration),
         ]
     }
 }
@@ -0,0 +1,141 @@
+use sea_orm_migration::{prelude::*, sea_orm::{prelude::Uuid, ConnectionTrait}};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BeatSaberVersions::Table)
+                    .add_column(ColumnDef::new(BeatSaberVersions::ReleaseDate).date().null())
+                    .add_column(
+                        ColumnDef::new(BeatSaberVersions::Deprecated)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(
+                        ColumnDef::new(BeatSaberVersions::IsLatest)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(BeatSaberVersionAliases::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BeatSaberVersionAliases::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BeatSaberVersionAliases::BeatSaberVersionId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BeatSaberVersionAliases::Alias)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_beat_saber_version_aliases_beat_saber_version")
+                            .from(
+                                BeatSaberVersionAliases::Table,
+                                BeatSaberVersionAliases::BeatSaberVersionId,
+                            )
+                            .to(BeatSaberVersions::Table, BeatSaberVersions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // mark the newest seeded version as the latest one
+        let db = manager.get_connection();
+        let rows = db
+            .query_all(
+                db.get_database_backend().build(
+                    Query::select()
+                        .columns([BeatSaberVersions::Id, BeatSaberVersions::Ver])
+                        .from(BeatSaberVersions::Table),
+                ),
+            )
+            .await?;
+
+        let mut latest: Option<(Uuid, semver::Version)> = None;
+        for row in rows {
+            let id: Uuid = row.try_get("", "id")?;
+            let ver: String = row.try_get("", "ver")?;
+            if let Ok(ver) = semver::Version::parse(&ver) {
+                if latest.as_ref().map_or(true, |(_, l)| ver > *l) {
+                    latest = Some((id, ver));
+                }
+            }
+        }
+
+        if let Some((id, _)) = latest {
+            manager
+                .exec_stmt(
+                    Query::update()
+                        .table(BeatSaberVersions::Table)
+                        .value(BeatSaberVersions::IsLatest, true)
+                        .and_where(Expr::col(BeatSaberVersions::Id).eq(id))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BeatSaberVersionAliases::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BeatSaberVersions::Table)
+                    .drop_column(BeatSaberVersions::ReleaseDate)
+                    .drop_column(BeatSaberVersions::Deprecated)
+                    .drop_column(BeatSaberVersions::IsLatest)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum BeatSaberVersions {
+    Table,
+    Id,
+    Ver,
+    ReleaseDate,
+    Deprecated,
+    IsLatest,
+}
+
+#[derive(Iden)]
+enum BeatSaberVersionAliases {
+    Table,
+    Id,
+    BeatSaberVersionId,
+    Alias,
+}
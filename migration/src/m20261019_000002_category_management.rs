@@ -0,0 +1,138 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Categories::Table)
+                    .add_column(
+                        ColumnDef::new(Categories::Retired)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ModCategories::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ModCategories::ModId).uuid().not_null())
+                    .col(ColumnDef::new(ModCategories::CategoryId).uuid().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(ModCategories::ModId)
+                            .col(ModCategories::CategoryId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mod_categories_mod")
+                            .from(ModCategories::Table, ModCategories::ModId)
+                            .to(Mods::Table, Mods::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mod_categories_category")
+                            .from(ModCategories::Table, ModCategories::CategoryId)
+                            .to(Categories::Table, Categories::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ModTags::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ModTags::ModId).uuid().not_null())
+                    .col(ColumnDef::new(ModTags::Tag).string().not_null())
+                    .primary_key(Index::create().col(ModTags::ModId).col(ModTags::Tag))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mod_tags_mod")
+                            .from(ModTags::Table, ModTags::ModId)
+                            .to(Mods::Table, Mods::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // every existing mod keeps its current category
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(ModCategories::Table)
+                    .columns([ModCategories::ModId, ModCategories::CategoryId])
+                    .select_from(
+                        Query::select()
+                            .columns([Mods::Id, Mods::Category])
+                            .from(Mods::Table)
+                            .to_owned(),
+                    )
+                    .map_err(|e| DbErr::Custom(e.to_string()))?
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ModTags::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ModCategories::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Categories::Table)
+                    .drop_column(Categories::Retired)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Categories {
+    Table,
+    Id,
+    Retired,
+}
+
+#[derive(Iden)]
+enum Mods {
+    Table,
+    Id,
+    Category,
+}
+
+#[derive(Iden)]
+enum ModCategories {
+    Table,
+    ModId,
+    CategoryId,
+}
+
+#[derive(Iden)]
+enum ModTags {
+    Table,
+    ModId,
+    Tag,
+}
@@ -0,0 +1,76 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .modify_column(ColumnDef::new(Users::GithubId).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // ids past i32::MAX were stored wrapped around, and got copied into user_identities
+        db.execute_unprepared(
+            "UPDATE user_identities
+             SET provider_user_id = (provider_user_id::bigint + 4294967296)::text
+             WHERE provider = 'github' AND provider_user_id LIKE '-%'",
+        )
+        .await?;
+
+        // identities created since then carry the real id, so they win over the column
+        db.execute_unprepared(
+            "UPDATE users
+             SET github_id = user_identities.provider_user_id::bigint
+             FROM user_identities
+             WHERE user_identities.user_id = users.id
+               AND user_identities.provider = 'github'
+               AND users.github_id IS DISTINCT FROM user_identities.provider_user_id::bigint",
+        )
+        .await?;
+
+        let remaining = db
+            .query_one(sea_orm_migration::sea_orm::Statement::from_string(
+                manager.get_database_backend(),
+                "SELECT COUNT(*) AS count FROM users WHERE github_id < 0".to_string(),
+            ))
+            .await?
+            .map(|row| row.try_get::<i64>("", "count"))
+            .transpose()?
+            .unwrap_or(0);
+
+        if remaining > 0 {
+            return Err(DbErr::Migration(format!(
+                "{} users still have a negative github id",
+                remaining
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .modify_column(ColumnDef::new(Users::GithubId).integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Users {
+    Table,
+    GithubId,
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // every package uploaded so far was a mod, anything else was rejected
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Mods::Table)
+                    .add_column(
+                        ColumnDef::new(Mods::ModType)
+                            .string()
+                            .not_null()
+                            .default("mod"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_mods_mod_type")
+                    .table(Mods::Table)
+                    .col(Mods::ModType)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Mods::Table)
+                    .drop_column(Mods::ModType)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Mods {
+    Table,
+    ModType,
+}
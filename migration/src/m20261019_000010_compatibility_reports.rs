@@ -0,0 +1,137 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CompatibilityReports::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CompatibilityReports::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(CompatibilityReports::VersionId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(CompatibilityReports::BeatSaberVersionId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(CompatibilityReports::UserId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(CompatibilityReports::Works)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(CompatibilityReports::Notes).text().null())
+                    .col(
+                        ColumnDef::new(CompatibilityReports::CreatedAt)
+                            .date_time()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(CompatibilityReports::UpdatedAt)
+                            .date_time()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_compatibility_reports_version")
+                            .from(CompatibilityReports::Table, CompatibilityReports::VersionId)
+                            .to(Versions::Table, Versions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_compatibility_reports_beat_saber_version")
+                            .from(
+                                CompatibilityReports::Table,
+                                CompatibilityReports::BeatSaberVersionId,
+                            )
+                            .to(BeatSaberVersions::Table, BeatSaberVersions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_compatibility_reports_user")
+                            .from(CompatibilityReports::Table, CompatibilityReports::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // one report per user and pair, reporting again replaces it
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_compatibility_reports_version_game_user")
+                    .table(CompatibilityReports::Table)
+                    .col(CompatibilityReports::VersionId)
+                    .col(CompatibilityReports::BeatSaberVersionId)
+                    .col(CompatibilityReports::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CompatibilityReports::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum CompatibilityReports {
+    Table,
+    Id,
+    VersionId,
+    BeatSaberVersionId,
+    UserId,
+    Works,
+    Notes,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum Versions {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum BeatSaberVersions {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
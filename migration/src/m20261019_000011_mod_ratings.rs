@@ -0,0 +1,212 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ModRatings::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ModRatings::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ModRatings::ModId).uuid().not_null())
+                    .col(ColumnDef::new(ModRatings::UserId).uuid().not_null())
+                    .col(ColumnDef::new(ModRatings::VersionId).uuid().null())
+                    .col(ColumnDef::new(ModRatings::Rating).integer().not_null())
+                    .col(ColumnDef::new(ModRatings::Review).text().null())
+                    .col(ColumnDef::new(ModRatings::Reply).text().null())
+                    .col(ColumnDef::new(ModRatings::RepliedAt).date_time().null())
+                    .col(
+                        ColumnDef::new(ModRatings::Hidden)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(ModRatings::CreatedAt)
+                            .date_time()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(ModRatings::UpdatedAt)
+                            .date_time()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mod_ratings_mod")
+                            .from(ModRatings::Table, ModRatings::ModId)
+                            .to(Mods::Table, Mods::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mod_ratings_user")
+                            .from(ModRatings::Table, ModRatings::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mod_ratings_version")
+                            .from(ModRatings::Table, ModRatings::VersionId)
+                            .to(Versions::Table, Versions::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // one rating per user and mod, rating again replaces it
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_mod_ratings_mod_user")
+                    .table(ModRatings::Table)
+                    .col(ModRatings::ModId)
+                    .col(ModRatings::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_mod_ratings_version")
+                    .table(ModRatings::Table)
+                    .col(ModRatings::VersionId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ModStats::Table)
+                    .add_column(
+                        ColumnDef::new(ModStats::Rating)
+                            .double()
+                            .not_null()
+                            .default(0.0),
+                    )
+                    .add_column(
+                        ColumnDef::new(ModStats::RatingCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(VersionStats::Table)
+                    .add_column(
+                        ColumnDef::new(VersionStats::Rating)
+                            .double()
+                            .not_null()
+                            .default(0.0),
+                    )
+                    .add_column(
+                        ColumnDef::new(VersionStats::RatingCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(VersionStats::Table)
+                    .drop_column(VersionStats::Rating)
+                    .drop_column(VersionStats::RatingCount)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ModStats::Table)
+                    .drop_column(ModStats::Rating)
+                    .drop_column(ModStats::RatingCount)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ModRatings::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum ModRatings {
+    Table,
+    Id,
+    ModId,
+    UserId,
+    VersionId,
+    Rating,
+    Review,
+    Reply,
+    RepliedAt,
+    Hidden,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+enum ModStats {
+    Table,
+    Rating,
+    RatingCount,
+}
+
+#[derive(Iden)]
+enum VersionStats {
+    Table,
+    Rating,
+    RatingCount,
+}
+
+#[derive(Iden)]
+enum Mods {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Versions {
+    Table,
+    Id,
+}
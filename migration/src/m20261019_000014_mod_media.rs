@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ModMedia::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ModMedia::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ModMedia::ModId).uuid().not_null())
+                    .col(ColumnDef::new(ModMedia::Kind).string().not_null())
+                    .col(ColumnDef::new(ModMedia::Url).string().not_null())
+                    .col(ColumnDef::new(ModMedia::Thumbnail).string().null())
+                    .col(ColumnDef::new(ModMedia::Caption).string().null())
+                    .col(ColumnDef::new(ModMedia::Position).integer().not_null())
+                    .col(
+                        ColumnDef::new(ModMedia::CreatedAt)
+                            .date_time()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mod_media_mod")
+                            .from(ModMedia::Table, ModMedia::ModId)
+                            .to(Mods::Table, Mods::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_mod_media_mod_position")
+                    .table(ModMedia::Table)
+                    .col(ModMedia::ModId)
+                    .col(ModMedia::Position)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ModMedia::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum ModMedia {
+    Table,
+    Id,
+    ModId,
+    Kind,
+    Url,
+    Thumbnail,
+    Caption,
+    Position,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum Mods {
+    Table,
+    Id,
+}
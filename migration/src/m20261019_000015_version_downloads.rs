@@ -0,0 +1,131 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(VersionDownloads::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(VersionDownloads::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(VersionDownloads::VersionId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(VersionDownloads::BeatSaberVersionId)
+                            .uuid()
+                            .null(),
+                    )
+                    .col(ColumnDef::new(VersionDownloads::Day).date().not_null())
+                    .col(
+                        ColumnDef::new(VersionDownloads::Downloads)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_version_downloads_version")
+                            .from(VersionDownloads::Table, VersionDownloads::VersionId)
+                            .to(Versions::Table, Versions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_version_downloads_beat_saber_version")
+                            .from(
+                                VersionDownloads::Table,
+                                VersionDownloads::BeatSaberVersionId,
+                            )
+                            .to(BeatSaberVersions::Table, BeatSaberVersions::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // one row per version, game version and day; downloads without a known game version
+        // share the row of the nil uuid
+        db.execute_unprepared(
+            r#"CREATE UNIQUE INDEX idx_version_downloads_bucket ON version_downloads (
+                   version_id,
+                   COALESCE(beat_saber_version_id, '00000000-0000-0000-0000-000000000000'::uuid),
+                   day
+               )"#,
+        )
+        .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_version_downloads_day")
+                    .table(VersionDownloads::Table)
+                    .col(VersionDownloads::Day)
+                    .to_owned(),
+            )
+            .await?;
+
+        // downloads counted before there was a history are put on the day the version was
+        // uploaded, so the history always adds up to the totals
+        db.execute_unprepared(
+            r#"INSERT INTO version_downloads (version_id, day, downloads)
+               SELECT versions.id, versions.created_at::date, version_stats.downloads
+               FROM versions JOIN version_stats ON version_stats.id = versions.stats
+               WHERE version_stats.downloads > 0"#,
+        )
+        .await?;
+
+        db.execute_unprepared(
+            r#"UPDATE mod_stats SET downloads = COALESCE((
+                   SELECT SUM(version_stats.downloads)
+                   FROM versions JOIN version_stats ON version_stats.id = versions.stats
+                   WHERE versions.mod_id = mods.id
+               ), 0)
+               FROM mods WHERE mods.stats = mod_stats.id"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(VersionDownloads::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum VersionDownloads {
+    Table,
+    Id,
+    VersionId,
+    BeatSaberVersionId,
+    Day,
+    Downloads,
+}
+
+#[derive(Iden)]
+enum Versions {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum BeatSaberVersions {
+    Table,
+    Id,
+}
@@ -0,0 +1,30 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// bits of the api's `Permission`
+const CREATE_MOD: i32 = 1 << 2;
+const EDIT_MOD: i32 = 1 << 3;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // accounts were created without `EDIT_MOD`, so authors couldn't edit the mods they
+        // published. anyone who may publish mods may now edit their own
+        manager
+            .get_connection()
+            .execute_unprepared(&format!(
+                "UPDATE users SET permissions = permissions | {EDIT_MOD}
+                 WHERE permissions & {CREATE_MOD} <> 0"
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // there's no telling which users held `EDIT_MOD` before
+        Ok(())
+    }
+}
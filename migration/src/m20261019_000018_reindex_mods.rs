@@ -0,0 +1,27 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // search documents written before mods had a type, categories, tags and ratings lack
+        // those fields, so filtering on them leaves the mods out. queue an `index_mod` job for
+        // every mod to rewrite them
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "INSERT INTO jobs (kind, payload)
+                 SELECT 'index_mod', jsonb_build_object('mod_id', id) FROM mods",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // the rewritten documents are still valid
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(VersionDownloadRollups::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(VersionDownloadRollups::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(VersionDownloadRollups::VersionId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(VersionDownloadRollups::BeatSaberVersionId)
+                            .uuid()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(VersionDownloadRollups::Granularity)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(VersionDownloadRollups::Period)
+                            .date()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(VersionDownloadRollups::Downloads)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_version_download_rollups_version")
+                            .from(
+                                VersionDownloadRollups::Table,
+                                VersionDownloadRollups::VersionId,
+                            )
+                            .to(Versions::Table, Versions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_version_download_rollups_beat_saber_version")
+                            .from(
+                                VersionDownloadRollups::Table,
+                                VersionDownloadRollups::BeatSaberVersionId,
+                            )
+                            .to(BeatSaberVersions::Table, BeatSaberVersions::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // same buckets as `version_downloads`, per week or month instead of per day
+        db.execute_unprepared(
+            r#"CREATE UNIQUE INDEX idx_version_download_rollups_bucket ON version_download_rollups (
+                   version_id,
+                   COALESCE(beat_saber_version_id, '00000000-0000-0000-0000-000000000000'::uuid),
+                   granularity,
+                   period
+               )"#,
+        )
+        .await?;
+
+        for granularity in ["week", "month"] {
+            db.execute_unprepared(&format!(
+                r#"INSERT INTO version_download_rollups
+                       (version_id, beat_saber_version_id, granularity, period, downloads)
+                   SELECT version_id, beat_saber_version_id, '{granularity}',
+                          date_trunc('{granularity}', day)::date, SUM(downloads)
+                   FROM version_downloads
+                   GROUP BY version_id, beat_saber_version_id, date_trunc('{granularity}', day)"#
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(VersionDownloadRollups::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum VersionDownloadRollups {
+    Table,
+    Id,
+    VersionId,
+    BeatSaberVersionId,
+    Granularity,
+    Period,
+    Downloads,
+}
+
+#[derive(Iden)]
+enum Versions {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum BeatSaberVersions {
+    Table,
+    Id,
+}
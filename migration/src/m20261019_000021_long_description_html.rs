@@ -0,0 +1,49 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Mods::Table)
+                    .add_column(ColumnDef::new(Mods::LongDescriptionHtml).text().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // the markdown renderer lives in the api, so existing descriptions are rendered by a
+        // `render_description` job each
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "INSERT INTO jobs (kind, payload)
+                 SELECT 'render_description', jsonb_build_object('mod_id', id) FROM mods
+                 WHERE long_description IS NOT NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Mods::Table)
+                    .drop_column(Mods::LongDescriptionHtml)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Mods {
+    Table,
+    LongDescriptionHtml,
+}
@@ -0,0 +1,797 @@
+//! Static analysis of the .NET assemblies mods ship.
+//!
+//! Only the PE headers and the metadata tables are read, the IL is never looked at. That is
+//! enough to see which assemblies, types and native functions a mod references.
+
+use std::fmt;
+
+use juniper::{GraphQLEnum, GraphQLObject};
+use serde::{Deserialize, Serialize};
+
+/// What a flagged reference could be used for.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingCategory {
+    /// Starting other programs.
+    Process,
+    /// Raw network access, bypassing the game's own networking.
+    Sockets,
+    Registry,
+    /// The artifact could not be analysed, so nothing is known about it.
+    Unreadable,
+}
+
+#[derive(GraphQLObject, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    pub category: FindingCategory,
+    /// The type or native function that was referenced.
+    pub reference: String,
+}
+
+/// A function imported from a native library through P/Invoke.
+#[derive(GraphQLObject, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NativeImport {
+    pub module: String,
+    pub entry_point: String,
+}
+
+#[derive(GraphQLObject, Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalysisReport {
+    /// Name the assembly gives itself.
+    pub assembly: Option<String>,
+    pub referenced_assemblies: Vec<String>,
+    pub native_imports: Vec<NativeImport>,
+    pub findings: Vec<Finding>,
+    /// Referenced assemblies that neither the game, the mod itself nor its dependencies ship.
+    #[serde(default)]
+    pub undeclared_references: Vec<String>,
+}
+
+impl AnalysisReport {
+    /// Whether anything was flagged. Risky versions are never approved automatically.
+    pub fn is_risky(&self) -> bool {
+        !self.findings.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnalysisError {
+    NotPe,
+    NotDotNet,
+    Malformed(&'static str),
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotPe => write!(f, "not a PE file"),
+            Self::NotDotNet => write!(f, "not a .NET assembly"),
+            Self::Malformed(what) => write!(f, "malformed {}", what),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {}
+
+/// Analyses a mod's dll. Files that can't be read are reported as such instead of failing, so
+/// they are held for review like any other risky upload.
+pub fn analyse(dll: &[u8]) -> AnalysisReport {
+    match read_metadata(dll) {
+        Ok(metadata) => report(metadata),
+        Err(e) => AnalysisReport {
+            findings: vec![Finding {
+                category: FindingCategory::Unreadable,
+                reference: e.to_string(),
+            }],
+            ..Default::default()
+        },
+    }
+}
+
+/// Types whose use is flagged, as `(namespace, name prefix)`.
+const RISKY_TYPES: &[(FindingCategory, &str, &str)] = &[
+    (FindingCategory::Process, "System.Diagnostics", "Process"),
+    (FindingCategory::Sockets, "System.Net.Sockets", ""),
+    (FindingCategory::Registry, "Microsoft.Win32", "Registry"),
+];
+
+/// Native functions whose import is flagged, as `(library, entry point prefix)`.
+const RISKY_IMPORTS: &[(FindingCategory, &str, &str)] = &[
+    (FindingCategory::Process, "kernel32", "CreateProcess"),
+    (FindingCategory::Process, "kernel32", "WinExec"),
+    (FindingCategory::Process, "shell32", "ShellExecute"),
+    (FindingCategory::Sockets, "ws2_32", ""),
+    (FindingCategory::Sockets, "wsock32", ""),
+    (FindingCategory::Registry, "advapi32", "Reg"),
+];
+
+fn report(metadata: Metadata) -> AnalysisReport {
+    let mut findings = vec![];
+
+    for (namespace, name) in &metadata.type_refs {
+        for (category, ns, prefix) in RISKY_TYPES {
+            if namespace == ns && name.starts_with(prefix) {
+                add_finding(&mut findings, *category, format!("{}.{}", namespace, name));
+            }
+        }
+    }
+
+    for import in &metadata.native_imports {
+        let module = import.module.to_ascii_lowercase();
+        let module = module.trim_end_matches(".dll");
+        for (category, library, prefix) in RISKY_IMPORTS {
+            if module == *library && import.entry_point.starts_with(prefix) {
+                let reference = format!("{}!{}", import.module, import.entry_point);
+                add_finding(&mut findings, *category, reference);
+            }
+        }
+    }
+
+    AnalysisReport {
+        assembly: metadata.assembly,
+        referenced_assemblies: metadata.assembly_refs,
+        native_imports: metadata.native_imports,
+        findings,
+        undeclared_references: vec![],
+    }
+}
+
+/// Adds a finding unless the same reference was already flagged, which happens when a type
+/// or function is referenced more than once.
+fn add_finding(findings: &mut Vec<Finding>, category: FindingCategory, reference: String) {
+    let finding = Finding {
+        category,
+        reference,
+    };
+    if !findings.contains(&finding) {
+        findings.push(finding);
+    }
+}
+
+/// What happens to uploads referencing assemblies they don't declare, configured with
+/// `ASSEMBLY_CHECK`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssemblyCheck {
+    Off,
+    /// Record the references in the report, but accept the upload.
+    Warn,
+    Reject,
+}
+
+pub fn assembly_check() -> AssemblyCheck {
+    match std::env::var("ASSEMBLY_CHECK").as_deref() {
+        Ok("off") => AssemblyCheck::Off,
+        Ok("reject") => AssemblyCheck::Reject,
+        _ => AssemblyCheck::Warn,
+    }
+}
+
+/// Assemblies that come with the game or its runtime. A trailing `*` matches any suffix.
+const GAME_ASSEMBLIES: &[&str] = &[
+    "mscorlib",
+    "netstandard",
+    "System",
+    "System.*",
+    "Microsoft.*",
+    "Mono.*",
+    "UnityEngine",
+    "UnityEngine.*",
+    "Unity.*",
+    "Assembly-CSharp",
+    "Assembly-CSharp-firstpass",
+    "Main",
+    "HMLib",
+    "HMUI",
+    "HMRendering",
+    "BGLib.*",
+    "BeatmapCore",
+    "GameplayCore",
+    "DataModels",
+    "Colors",
+    "Zenject",
+    "Zenject-usage",
+    "Newtonsoft.Json",
+    "Oculus.*",
+    "Steamworks.NET",
+];
+
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name
+            .get(..prefix.len())
+            .map_or(false, |p| p.eq_ignore_ascii_case(prefix)),
+        None => pattern.eq_ignore_ascii_case(name),
+    }
+}
+
+/// Extra assemblies to accept everywhere, as a comma separated `ASSEMBLY_ALLOWLIST`.
+fn allowlist() -> Vec<String> {
+    std::env::var("ASSEMBLY_ALLOWLIST")
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Referenced assemblies of `report` that aren't in `declared`, the game's assemblies or the
+/// allowlist.
+pub fn undeclared_references(report: &AnalysisReport, declared: &[String]) -> Vec<String> {
+    let allowlist = allowlist();
+
+    report
+        .referenced_assemblies
+        .iter()
+        .filter(|name| {
+            !GAME_ASSEMBLIES
+                .iter()
+                .copied()
+                .chain(allowlist.iter().map(String::as_str))
+                .any(|p| matches_pattern(p, name))
+                && !declared.iter().any(|d| d.eq_ignore_ascii_case(name))
+                && report
+                    .assembly
+                    .as_ref()
+                    .map_or(true, |own| !own.eq_ignore_ascii_case(name))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The parts of an assembly's metadata the analysis looks at.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    pub assembly: Option<String>,
+    pub assembly_refs: Vec<String>,
+    /// `(namespace, name)` of every referenced type.
+    pub type_refs: Vec<(String, String)>,
+    pub native_imports: Vec<NativeImport>,
+}
+
+/// Bounds checked little endian reads.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn at(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn bytes(&mut self, n: usize, what: &'static str) -> Result<&'a [u8], AnalysisError> {
+        let end = self.pos.checked_add(n).ok_or(AnalysisError::Malformed(what))?;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or(AnalysisError::Malformed(what))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self, what: &'static str) -> Result<u8, AnalysisError> {
+        Ok(self.bytes(1, what)?[0])
+    }
+
+    fn u16(&mut self, what: &'static str) -> Result<u16, AnalysisError> {
+        let b = self.bytes(2, what)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self, what: &'static str) -> Result<u32, AnalysisError> {
+        let b = self.bytes(4, what)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self, what: &'static str) -> Result<u64, AnalysisError> {
+        Ok(self.u32(what)? as u64 | (self.u32(what)? as u64) << 32)
+    }
+
+    /// Reads a 2 or 4 byte index.
+    fn index(&mut self, size: usize, what: &'static str) -> Result<u32, AnalysisError> {
+        match size {
+            2 => Ok(self.u16(what)? as u32),
+            _ => self.u32(what),
+        }
+    }
+
+    fn skip(&mut self, n: usize, what: &'static str) -> Result<(), AnalysisError> {
+        self.bytes(n, what).map(|_| ())
+    }
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_offset: u32,
+    raw_size: u32,
+}
+
+fn rva_to_offset(sections: &[Section], rva: u32) -> Result<usize, AnalysisError> {
+    sections
+        .iter()
+        .find(|s| {
+            rva >= s.virtual_address
+                && rva - s.virtual_address < s.virtual_size.max(s.raw_size)
+        })
+        .map(|s| (rva - s.virtual_address) as usize + s.raw_offset as usize)
+        .ok_or(AnalysisError::Malformed("section table"))
+}
+
+/// Finds the metadata root through the PE and CLI headers.
+fn metadata_root(pe: &[u8]) -> Result<&[u8], AnalysisError> {
+    if pe.get(..2) != Some(b"MZ") {
+        return Err(AnalysisError::NotPe);
+    }
+    let pe_offset = Reader::at(pe, 0x3c).u32("DOS header")? as usize;
+
+    let mut r = Reader::at(pe, pe_offset);
+    if r.bytes(4, "PE signature")? != b"PE\0\0" {
+        return Err(AnalysisError::NotPe);
+    }
+    r.skip(2, "COFF header")?;
+    let section_count = r.u16("COFF header")?;
+    r.skip(12, "COFF header")?;
+    let optional_size = r.u16("COFF header")? as usize;
+    r.skip(2, "COFF header")?;
+
+    let optional = r.pos;
+    // the data directories sit further back in PE32+ headers
+    let directories = match r.u16("optional header")? {
+        0x10b => optional + 96,
+        0x20b => optional + 112,
+        _ => return Err(AnalysisError::Malformed("optional header")),
+    };
+    let directory_count = Reader::at(pe, directories - 4).u32("optional header")?;
+    // the CLI header is the 15th directory
+    if directory_count < 15 {
+        return Err(AnalysisError::NotDotNet);
+    }
+    let mut r = Reader::at(pe, directories + 14 * 8);
+    let cli_rva = r.u32("data directories")?;
+    if cli_rva == 0 {
+        return Err(AnalysisError::NotDotNet);
+    }
+
+    let mut r = Reader::at(pe, optional + optional_size);
+    let mut sections = vec![];
+    for _ in 0..section_count {
+        r.skip(8, "section table")?;
+        let virtual_size = r.u32("section table")?;
+        let virtual_address = r.u32("section table")?;
+        let raw_size = r.u32("section table")?;
+        let raw_offset = r.u32("section table")?;
+        r.skip(16, "section table")?;
+        sections.push(Section {
+            virtual_address,
+            virtual_size,
+            raw_offset,
+            raw_size,
+        });
+    }
+
+    let mut r = Reader::at(pe, rva_to_offset(&sections, cli_rva)? + 8);
+    let metadata_rva = r.u32("CLI header")?;
+    let metadata_size = r.u32("CLI header")? as usize;
+
+    let start = rva_to_offset(&sections, metadata_rva)?;
+    Reader::at(pe, start).bytes(metadata_size, "metadata")
+}
+
+/// Heap index sizes and row counts of a `#~` stream.
+struct Tables {
+    string_size: usize,
+    guid_size: usize,
+    blob_size: usize,
+    rows: [u32; TABLE_COUNT],
+}
+
+const TABLE_COUNT: usize = 0x2d;
+
+const MODULE: usize = 0x00;
+const TYPE_REF: usize = 0x01;
+const TYPE_DEF: usize = 0x02;
+const FIELD: usize = 0x04;
+const METHOD_DEF: usize = 0x06;
+const PARAM: usize = 0x08;
+const INTERFACE_IMPL: usize = 0x09;
+const MEMBER_REF: usize = 0x0a;
+const DECL_SECURITY: usize = 0x0e;
+const STAND_ALONE_SIG: usize = 0x11;
+const EVENT: usize = 0x14;
+const PROPERTY: usize = 0x17;
+const MODULE_REF: usize = 0x1a;
+const TYPE_SPEC: usize = 0x1b;
+const IMPL_MAP: usize = 0x1c;
+const ASSEMBLY: usize = 0x20;
+const ASSEMBLY_REF: usize = 0x23;
+const FILE: usize = 0x26;
+const EXPORTED_TYPE: usize = 0x27;
+const MANIFEST_RESOURCE: usize = 0x28;
+const GENERIC_PARAM: usize = 0x2a;
+const METHOD_SPEC: usize = 0x2b;
+const GENERIC_PARAM_CONSTRAINT: usize = 0x2c;
+
+const TYPE_DEF_OR_REF: &[usize] = &[TYPE_DEF, TYPE_REF, TYPE_SPEC];
+const HAS_CONSTANT: &[usize] = &[FIELD, PARAM, PROPERTY];
+const HAS_CUSTOM_ATTRIBUTE: &[usize] = &[
+    METHOD_DEF,
+    FIELD,
+    TYPE_REF,
+    TYPE_DEF,
+    PARAM,
+    INTERFACE_IMPL,
+    MEMBER_REF,
+    MODULE,
+    DECL_SECURITY,
+    PROPERTY,
+    EVENT,
+    STAND_ALONE_SIG,
+    MODULE_REF,
+    TYPE_SPEC,
+    ASSEMBLY,
+    ASSEMBLY_REF,
+    FILE,
+    EXPORTED_TYPE,
+    MANIFEST_RESOURCE,
+    GENERIC_PARAM,
+    GENERIC_PARAM_CONSTRAINT,
+    METHOD_SPEC,
+];
+const HAS_FIELD_MARSHAL: &[usize] = &[FIELD, PARAM];
+const HAS_DECL_SECURITY: &[usize] = &[TYPE_DEF, METHOD_DEF, ASSEMBLY];
+const MEMBER_REF_PARENT: &[usize] = &[TYPE_DEF, TYPE_REF, MODULE_REF, METHOD_DEF, TYPE_SPEC];
+const HAS_SEMANTICS: &[usize] = &[EVENT, PROPERTY];
+const METHOD_DEF_OR_REF: &[usize] = &[METHOD_DEF, MEMBER_REF];
+const MEMBER_FORWARDED: &[usize] = &[FIELD, METHOD_DEF];
+const IMPLEMENTATION: &[usize] = &[FILE, ASSEMBLY_REF, EXPORTED_TYPE];
+// three of the five tags are unused
+const CUSTOM_ATTRIBUTE_TYPE: &[usize] = &[METHOD_DEF, MEMBER_REF, METHOD_DEF, METHOD_DEF, METHOD_DEF];
+const RESOLUTION_SCOPE: &[usize] = &[MODULE, MODULE_REF, ASSEMBLY_REF, TYPE_REF];
+const TYPE_OR_METHOD_DEF: &[usize] = &[TYPE_DEF, METHOD_DEF];
+
+/// One column of a metadata table.
+enum Col {
+    Fixed(usize),
+    Str,
+    Guid,
+    Blob,
+    Table(usize),
+    Coded(&'static [usize]),
+}
+
+impl Tables {
+    fn table_index_size(&self, table: usize) -> usize {
+        if self.rows[table] < 1 << 16 {
+            2
+        } else {
+            4
+        }
+    }
+
+    fn coded_index_size(&self, tables: &[usize]) -> usize {
+        let tag_bits = usize::BITS - (tables.len() - 1).leading_zeros();
+        let max_rows = tables.iter().map(|t| self.rows[*t]).max().unwrap_or(0);
+        if max_rows < 1 << (16 - tag_bits) {
+            2
+        } else {
+            4
+        }
+    }
+
+    fn col_size(&self, col: &Col) -> usize {
+        match col {
+            Col::Fixed(n) => *n,
+            Col::Str => self.string_size,
+            Col::Guid => self.guid_size,
+            Col::Blob => self.blob_size,
+            Col::Table(t) => self.table_index_size(*t),
+            Col::Coded(tables) => self.coded_index_size(tables),
+        }
+    }
+
+    fn row_size(&self, table: usize) -> usize {
+        columns(table).iter().map(|c| self.col_size(c)).sum()
+    }
+}
+
+/// Columns of each table, from ECMA-335 II.22.
+fn columns(table: usize) -> Vec<Col> {
+    use Col::*;
+
+    match table {
+        0x00 => vec![Fixed(2), Str, Guid, Guid, Guid],
+        0x01 => vec![Coded(RESOLUTION_SCOPE), Str, Str],
+        0x02 => vec![
+            Fixed(4),
+            Str,
+            Str,
+            Coded(TYPE_DEF_OR_REF),
+            Table(FIELD),
+            Table(METHOD_DEF),
+        ],
+        0x03 => vec![Table(FIELD)],
+        0x04 => vec![Fixed(2), Str, Blob],
+        0x05 => vec![Table(METHOD_DEF)],
+        0x06 => vec![Fixed(4), Fixed(2), Fixed(2), Str, Blob, Table(PARAM)],
+        0x07 => vec![Table(PARAM)],
+        0x08 => vec![Fixed(2), Fixed(2), Str],
+        0x09 => vec![Table(TYPE_DEF), Coded(TYPE_DEF_OR_REF)],
+        0x0a => vec![Coded(MEMBER_REF_PARENT), Str, Blob],
+        0x0b => vec![Fixed(2), Coded(HAS_CONSTANT), Blob],
+        0x0c => vec![
+            Coded(HAS_CUSTOM_ATTRIBUTE),
+            Coded(CUSTOM_ATTRIBUTE_TYPE),
+            Blob,
+        ],
+        0x0d => vec![Coded(HAS_FIELD_MARSHAL), Blob],
+        0x0e => vec![Fixed(2), Coded(HAS_DECL_SECURITY), Blob],
+        0x0f => vec![Fixed(2), Fixed(4), Table(TYPE_DEF)],
+        0x10 => vec![Fixed(4), Table(FIELD)],
+        0x11 => vec![Blob],
+        0x12 => vec![Table(TYPE_DEF), Table(EVENT)],
+        0x13 => vec![Table(EVENT)],
+        0x14 => vec![Fixed(2), Str, Coded(TYPE_DEF_OR_REF)],
+        0x15 => vec![Table(TYPE_DEF), Table(PROPERTY)],
+        0x16 => vec![Table(PROPERTY)],
+        0x17 => vec![Fixed(2), Str, Blob],
+        0x18 => vec![Fixed(2), Table(METHOD_DEF), Coded(HAS_SEMANTICS)],
+        0x19 => vec![
+            Table(TYPE_DEF),
+            Coded(METHOD_DEF_OR_REF),
+            Coded(METHOD_DEF_OR_REF),
+        ],
+        0x1a => vec![Str],
+        0x1b => vec![Blob],
+        0x1c => vec![Fixed(2), Coded(MEMBER_FORWARDED), Str, Table(MODULE_REF)],
+        0x1d => vec![Fixed(4), Table(FIELD)],
+        0x1e => vec![Fixed(4), Fixed(4)],
+        0x1f => vec![Fixed(4)],
+        0x20 => vec![Fixed(4), Fixed(8), Fixed(4), Blob, Str, Str],
+        0x21 => vec![Fixed(4)],
+        0x22 => vec![Fixed(12)],
+        0x23 => vec![Fixed(8), Fixed(4), Blob, Str, Str, Blob],
+        0x24 => vec![Fixed(4), Table(ASSEMBLY_REF)],
+        0x25 => vec![Fixed(12), Table(ASSEMBLY_REF)],
+        0x26 => vec![Fixed(4), Str, Blob],
+        0x27 => vec![Fixed(4), Fixed(4), Str, Str, Coded(IMPLEMENTATION)],
+        0x28 => vec![Fixed(4), Fixed(4), Str, Coded(IMPLEMENTATION)],
+        0x29 => vec![Table(TYPE_DEF), Table(TYPE_DEF)],
+        0x2a => vec![Fixed(2), Fixed(2), Coded(TYPE_OR_METHOD_DEF), Str],
+        0x2b => vec![Coded(METHOD_DEF_OR_REF), Blob],
+        0x2c => vec![Table(GENERIC_PARAM), Coded(TYPE_DEF_OR_REF)],
+        _ => vec![],
+    }
+}
+
+/// Reads a string out of the `#Strings` heap.
+fn heap_string(strings: &[u8], index: u32) -> Result<String, AnalysisError> {
+    let rest = strings
+        .get(index as usize..)
+        .ok_or(AnalysisError::Malformed("string heap"))?;
+    let end = rest
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or(AnalysisError::Malformed("string heap"))?;
+    Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+}
+
+/// Reads the assembly's name and the references it makes from the metadata tables.
+pub fn read_metadata(pe: &[u8]) -> Result<Metadata, AnalysisError> {
+    let root = metadata_root(pe)?;
+
+    let mut r = Reader::at(root, 0);
+    if r.u32("metadata root")? != 0x424a_5342 {
+        return Err(AnalysisError::Malformed("metadata root"));
+    }
+    r.skip(8, "metadata root")?;
+    let version_len = r.u32("metadata root")? as usize;
+    r.skip(version_len + 2, "metadata root")?;
+    let stream_count = r.u16("metadata root")?;
+
+    let mut tables_stream = None;
+    let mut strings = None;
+    for _ in 0..stream_count {
+        let offset = r.u32("stream header")? as usize;
+        let size = r.u32("stream header")? as usize;
+        let name_start = r.pos;
+        while r.u8("stream header")? != 0 {}
+        let name = &root[name_start..r.pos - 1];
+        // names are padded to four bytes
+        r.pos = (r.pos + 3) & !3;
+
+        let stream = Reader::at(root, offset).bytes(size, "stream")?;
+        match name {
+            b"#~" | b"#-" => tables_stream = Some(stream),
+            b"#Strings" => strings = Some(stream),
+            _ => {}
+        }
+    }
+    let tables_stream = tables_stream.ok_or(AnalysisError::Malformed("metadata streams"))?;
+    let strings = strings.ok_or(AnalysisError::Malformed("metadata streams"))?;
+
+    let mut r = Reader::at(tables_stream, 6);
+    let heap_sizes = r.u8("table stream")?;
+    r.skip(1, "table stream")?;
+    let valid = r.u64("table stream")?;
+    r.skip(8, "table stream")?;
+
+    if valid >> TABLE_COUNT != 0 {
+        return Err(AnalysisError::Malformed("table stream"));
+    }
+
+    let mut tables = Tables {
+        string_size: if heap_sizes & 0x01 != 0 { 4 } else { 2 },
+        guid_size: if heap_sizes & 0x02 != 0 { 4 } else { 2 },
+        blob_size: if heap_sizes & 0x04 != 0 { 4 } else { 2 },
+        rows: [0; TABLE_COUNT],
+    };
+    for (table, rows) in tables.rows.iter_mut().enumerate() {
+        if valid & (1 << table) != 0 {
+            *rows = r.u32("table stream")?;
+        }
+    }
+
+    let mut offsets = [0; TABLE_COUNT];
+    let mut offset = r.pos;
+    for (table, start) in offsets.iter_mut().enumerate() {
+        *start = offset;
+        offset = tables
+            .row_size(table)
+            .checked_mul(tables.rows[table] as usize)
+            .and_then(|size| offset.checked_add(size))
+            .ok_or(AnalysisError::Malformed("table stream"))?;
+    }
+    if offset > tables_stream.len() {
+        return Err(AnalysisError::Malformed("table stream"));
+    }
+
+    let string = |r: &mut Reader| -> Result<String, AnalysisError> {
+        let index = r.index(tables.string_size, "table row")?;
+        heap_string(strings, index)
+    };
+
+    let mut metadata = Metadata::default();
+
+    let mut r = Reader::at(tables_stream, offsets[TYPE_REF]);
+    for _ in 0..tables.rows[TYPE_REF] {
+        r.skip(tables.coded_index_size(RESOLUTION_SCOPE), "table row")?;
+        let name = string(&mut r)?;
+        let namespace = string(&mut r)?;
+        metadata.type_refs.push((namespace, name));
+    }
+
+    let mut r = Reader::at(tables_stream, offsets[MODULE_REF]);
+    let mut module_refs = vec![];
+    for _ in 0..tables.rows[MODULE_REF] {
+        module_refs.push(string(&mut r)?);
+    }
+
+    let mut r = Reader::at(tables_stream, offsets[IMPL_MAP]);
+    for _ in 0..tables.rows[IMPL_MAP] {
+        r.skip(2 + tables.coded_index_size(MEMBER_FORWARDED), "table row")?;
+        let entry_point = string(&mut r)?;
+        let module = r.index(tables.table_index_size(MODULE_REF), "table row")? as usize;
+        // table indices start at 1
+        let module = module
+            .checked_sub(1)
+            .and_then(|i| module_refs.get(i))
+            .ok_or(AnalysisError::Malformed("ImplMap table"))?;
+        metadata.native_imports.push(NativeImport {
+            module: module.clone(),
+            entry_point,
+        });
+    }
+
+    if tables.rows[ASSEMBLY] > 0 {
+        let mut r = Reader::at(tables_stream, offsets[ASSEMBLY]);
+        r.skip(16 + tables.blob_size, "table row")?;
+        metadata.assembly = Some(string(&mut r)?);
+    }
+
+    let mut r = Reader::at(tables_stream, offsets[ASSEMBLY_REF]);
+    for _ in 0..tables.rows[ASSEMBLY_REF] {
+        r.skip(12 + tables.blob_size, "table row")?;
+        metadata.assembly_refs.push(string(&mut r)?);
+        r.skip(tables.string_size + tables.blob_size, "table row")?;
+    }
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A hand built assembly named `TestMod` that references `mscorlib`, the `Process` and
+    /// `Socket` types and imports `kernel32.dll!CreateProcessW`.
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/minimal.dll");
+
+    #[test]
+    fn reads_fixture() {
+        let report = analyse(FIXTURE);
+
+        assert_eq!(report.assembly.as_deref(), Some("TestMod"));
+        assert_eq!(report.referenced_assemblies, vec!["mscorlib".to_string()]);
+        assert_eq!(
+            report.native_imports,
+            vec![NativeImport {
+                module: "kernel32.dll".to_string(),
+                entry_point: "CreateProcessW".to_string(),
+            }]
+        );
+        let references = report
+            .findings
+            .iter()
+            .map(|f| (f.category, f.reference.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            references,
+            vec![
+                (FindingCategory::Process, "System.Diagnostics.Process"),
+                (FindingCategory::Sockets, "System.Net.Sockets.Socket"),
+                (FindingCategory::Process, "kernel32.dll!CreateProcessW"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_repeated_references_once() {
+        let type_ref = |namespace: &str, name: &str| (namespace.to_string(), name.to_string());
+        let report = report(Metadata {
+            type_refs: vec![
+                type_ref("System.Diagnostics", "Process"),
+                type_ref("System.Net.Sockets", "Socket"),
+                type_ref("System.Diagnostics", "Process"),
+            ],
+            ..Default::default()
+        });
+
+        assert_eq!(report.findings.len(), 2);
+    }
+
+    fn assert_unreadable(report: &AnalysisReport) {
+        assert!(report.is_risky());
+        assert_eq!(report.findings[0].category, FindingCategory::Unreadable);
+    }
+
+    #[test]
+    fn flags_truncated_files() {
+        for len in 0..FIXTURE.len() {
+            // the tail of the string heap can go without changing what's read
+            let report = analyse(&FIXTURE[..len]);
+            if report.assembly.is_none() {
+                assert_unreadable(&report);
+            }
+        }
+    }
+
+    #[test]
+    fn flags_garbage() {
+        assert_unreadable(&analyse(&[]));
+        assert_unreadable(&analyse(b"MZ"));
+        assert_unreadable(&analyse(&[0xff; 4096]));
+
+        // xorshift, so failures are reproducible
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut garbage = b"MZ".to_vec();
+        garbage.extend((0..4096).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }));
+        assert_unreadable(&analyse(&garbage));
+    }
+
+    #[test]
+    fn survives_corrupted_fixture() {
+        for i in 0..FIXTURE.len() {
+            for value in [0x00, 0x7f, 0xff] {
+                let mut dll = FIXTURE.to_vec();
+                dll[i] = value;
+                analyse(&dll);
+            }
+        }
+    }
+}
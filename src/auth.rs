@@ -1,4 +1,6 @@
+use actix_web::HttpRequest;
 use chrono::{DateTime, Utc};
+use juniper::{graphql_value, FieldError, FieldResult};
 use sea_orm::{EntityTrait, QueryFilter, ColumnTrait, DatabaseConnection};
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
@@ -18,9 +20,16 @@ bitflags::bitflags! {
         const EDIT_OTHER_USERS = 1 << 5;
         const EDIT_OTHER_MODS = 1 << 6;
         const VIEW_OTHER = 1 << 7;
+        const EDIT_SITE_DATA = 1 << 8;
     }
 }
 
+/// What new accounts may do: manage themselves and publish and edit their own mods.
+pub const DEFAULT_PERMISSIONS: Permission = Permission::VIEW_SELF
+    .union(Permission::EDIT_SELF)
+    .union(Permission::CREATE_MOD)
+    .union(Permission::EDIT_MOD);
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JWTAuth {
     pub user: entity::users::Model,
@@ -95,33 +104,36 @@ impl Authorization {
             Self::Session(s) => {
                 let auth = JWTAuth::decode(s.to_string(), *KEY.clone());
                 match auth {
-                    Some(auth) => {
-                        let user = entity::users::Entity::find_by_id(auth.user.id)
-                            .one(db)
-                            .await
-                            .unwrap()
-                            .unwrap();
-
-                        Some(user)
-                    },
+                    Some(auth) => entity::users::Entity::find_by_id(auth.user.id)
+                        .one(db)
+                        .await
+                        .ok()
+                        .flatten(),
                     None => None
                 }
             },
-            Self::ApiKey(uuid) => {
-                let user = entity::users::Entity::find()
-                    .filter(entity::users::Column::ApiKey.eq(sea_orm::prelude::Uuid::from_bytes(*uuid.as_bytes())))
-                    .one(db)
-                    .await
-                    .unwrap()
-                    .unwrap();
-
-                Some(user)
-            },
+            Self::ApiKey(uuid) => entity::users::Entity::find()
+                .filter(entity::users::Column::ApiKey.eq(sea_orm::prelude::Uuid::from_bytes(*uuid.as_bytes())))
+                .one(db)
+                .await
+                .ok()
+                .flatten(),
             _ => None
         }
     }
 }
 
+/// Resolves the user behind a `Bearer` `Authorization` header.
+pub async fn get_user_from_request(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+) -> Option<entity::users::Model> {
+    let header = req.headers().get("Authorization")?.to_str().ok()?;
+    let token = header.strip_prefix("Bearer ")?;
+
+    Authorization::parse(Some(token.to_string())).get_user(db).await
+}
+
 pub trait HasPermissions {
     fn permissions(&self) -> i32;
 }
@@ -164,4 +176,25 @@ impl HasPermissions for entity::users::Model {
 
 pub async fn validate_permissions<T: HasPermissions>(user: T, required: Permission) -> bool {
     required.bits() & user.permissions() != 0
-}
\ No newline at end of file
+}
+
+/// Resolves the user behind a GraphQL `auth` argument and checks that they hold `required`.
+pub async fn authorize(
+    db: &DatabaseConnection,
+    auth: Option<String>,
+    required: Permission,
+) -> FieldResult<entity::users::Model> {
+    match Authorization::parse(auth).get_user(db).await {
+        Some(user) if validate_permissions(&user, required).await => Ok(user),
+        _ => Err(FieldError::new(
+            "Unauthorized",
+            graphql_value!({ "auth": "Unauthorized" }),
+        )),
+    }
+}
+
+/// Authors may edit their own mods, moderators with `EDIT_OTHER_MODS` may edit any mod.
+pub async fn can_edit_mod(user: &entity::users::Model, m: &entity::mods::Model) -> bool {
+    (user.id == m.author && validate_permissions(user, Permission::EDIT_MOD).await)
+        || validate_permissions(user, Permission::EDIT_OTHER_MODS).await
+}
@@ -0,0 +1,157 @@
+use entity::prelude::*;
+use juniper::{graphql_value, FieldError, FieldResult, GraphQLObject};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, Set,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::search;
+
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GCategory {
+    pub name: String,
+    pub description: String,
+    pub retired: bool,
+}
+
+impl From<entity::categories::Model> for GCategory {
+    fn from(c: entity::categories::Model) -> Self {
+        GCategory {
+            name: c.name,
+            description: c.description,
+            retired: c.retired,
+        }
+    }
+}
+
+fn not_found() -> FieldError {
+    FieldError::new(
+        "Unknown category",
+        graphql_value!({ "category": "Unknown category" }),
+    )
+}
+
+fn validate_name(name: &str) -> FieldResult<()> {
+    if name.is_empty()
+        || name.len() > 32
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(FieldError::new(
+            "Category names must be 1-32 lowercase letters, digits or dashes",
+            graphql_value!({ "name": "Category names must be 1-32 lowercase letters, digits or dashes" }),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn find_by_name(
+    db: &DatabaseConnection,
+    name: &str,
+) -> FieldResult<entity::categories::Model> {
+    Categories::find()
+        .filter(entity::categories::Column::Name.eq(name))
+        .one(db)
+        .await?
+        .ok_or_else(not_found)
+}
+
+pub async fn find_all(db: &DatabaseConnection, include_retired: bool) -> FieldResult<Vec<GCategory>> {
+    let mut query = Categories::find();
+    if !include_retired {
+        query = query.filter(entity::categories::Column::Retired.eq(false));
+    }
+
+    Ok(query
+        .order_by_asc(entity::categories::Column::Name)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(GCategory::from)
+        .collect::<Vec<_>>())
+}
+
+pub async fn create(
+    db: &DatabaseConnection,
+    name: String,
+    description: String,
+) -> FieldResult<GCategory> {
+    validate_name(&name)?;
+
+    if find_by_name(db, &name).await.is_ok() {
+        return Err(FieldError::new(
+            "Category already exists",
+            graphql_value!({ "name": "Category already exists" }),
+        ));
+    }
+
+    Ok(entity::categories::ActiveModel {
+        name: Set(name),
+        description: Set(description),
+        retired: Set(false),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?
+    .into())
+}
+
+pub async fn update(
+    db: &DatabaseConnection,
+    name: String,
+    new_name: Option<String>,
+    description: Option<String>,
+) -> FieldResult<GCategory> {
+    let cata = find_by_name(db, &name).await?;
+    let mut am = cata.into_active_model();
+
+    if let Some(new_name) = new_name {
+        validate_name(&new_name)?;
+        if new_name != name && find_by_name(db, &new_name).await.is_ok() {
+            return Err(FieldError::new(
+                "Category already exists",
+                graphql_value!({ "name": "Category already exists" }),
+            ));
+        }
+        am.name = Set(new_name);
+    }
+
+    if let Some(description) = description {
+        am.description = Set(description);
+    }
+
+    let cata = am.update(db).await?;
+
+    if cata.name != name {
+        // search documents carry category names, so they need to follow the rename
+        let mod_ids = ModCategories::find()
+            .filter(entity::mod_categories::Column::CategoryId.eq(cata.id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|mc| mc.mod_id);
+
+        for mod_id in mod_ids {
+            if let Err(e) = search::index_mod(db, mod_id).await {
+                log::error!("failed to reindex mod {}: {}", mod_id, e);
+            }
+        }
+    }
+
+    Ok(cata.into())
+}
+
+pub async fn set_retired(
+    db: &DatabaseConnection,
+    name: String,
+    retired: bool,
+) -> FieldResult<GCategory> {
+    let cata = find_by_name(db, &name).await?;
+
+    let mut am = cata.into_active_model();
+    am.retired = Set(retired);
+
+    Ok(am.update(db).await?.into())
+}
@@ -1,11 +1,23 @@
-use actix_web::{get, post, web, Error, HttpResponse, Responder};
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use actix_web::{get, http::header, post, web, Error, HttpRequest, HttpResponse, Responder};
 use entity::prelude::*;
-use forge_lib::structs::{v1::{ManifestV1, unpack_v1_forgemod, ForgeModTypes, data, manifest}};
 
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use chrono::Utc;
+use futures::StreamExt;
+use sea_orm::{prelude::Uuid, ColumnTrait, EntityTrait, QueryFilter};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tempfile::SpooledTempFile;
 
-use crate::Database;
+use crate::{
+    downloads, game_versions,
+    images::{self, ImageKind, ProcessedImage, UploadError},
+    packages, Database,
+};
 
 #[derive(Copy, Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -14,11 +26,39 @@ enum CdnType {
     Package,
 }
 
+/// Lets mod managers say which game version a download is for, for the download history.
+#[derive(Debug, Deserialize)]
+struct DownloadQuery {
+    game_version: Option<String>,
+}
+
+/// Counts a download without holding up the response.
+fn count_download(db: &Database, version: entity::versions::Model, game_version: Option<String>) {
+    let db = db.pool.clone();
+    actix_web::rt::spawn(async move {
+        let game_version = match game_version {
+            Some(ver) => match game_versions::resolve(&db, &ver).await {
+                Ok(game_version) => game_version.map(|g| g.id),
+                Err(e) => {
+                    log::warn!("failed to resolve game version `{}`: {}", ver, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        if let Err(e) = downloads::record(&db, &version, game_version).await {
+            log::error!("failed to count download of {}: {}", version.id, e);
+        }
+    });
+}
+
 async fn cdn_handler(
     db: web::Data<Database>,
     slug: String,
     version: String,
     dl_type: CdnType,
+    game_version: Option<String>,
 ) -> impl Responder {
     let db_mod = Mods::find()
         .filter(entity::mods::Column::Slug.eq(&slug))
@@ -35,35 +75,29 @@ async fn cdn_handler(
             .unwrap();
 
         if let Some(db_version) = db_version {
-            let file = match std::fs::read(format!(
-                "./data/cdn/{}/{}.forgemod",
-                db_mod.id, db_version.id
-            )) {
+            let file = match std::fs::read(artifact_path(db_mod.id, db_version.id)) {
                 Ok(file) => file,
                 Err(_) => return HttpResponse::NotFound().finish(),
             };
             match dl_type {
                 CdnType::Dll => {
-                    // let dll = ForgeMod::try_from(&*file).unwrap().artifact_data;
-                    let package = unpack_v1_forgemod(&*file).unwrap();
-
-                    match package {
-                        ForgeModTypes::Mod(m) => 
-                        {
-                            return HttpResponse::Ok()
+                    // modules are plain files, only mods and libs carry a dll
+                    return match packages::unpack(&*file).and_then(|p| Some((p.id, p.artifact?))) {
+                        Some((id, dll)) => {
+                            count_download(&db, db_version, game_version);
+                            HttpResponse::Ok()
                                 .content_type("application/octet-stream")
                                 .append_header((
                                     "Content-Disposition",
-                                    format!("attachment; filename=\"{}.dll\"", m.manifest._id),
+                                    format!("attachment; filename=\"{}.dll\"", id),
                                 ))
-                                .body(m.data.artifact_data);
-                        },
-                        _ => {
-                            return HttpResponse::NotFound().finish();
+                                .body(dll)
                         }
-                    }
+                        None => HttpResponse::NotFound().finish(),
+                    };
                 }
                 CdnType::Package => {
+                    count_download(&db, db_version, game_version);
                     return HttpResponse::Ok()
                         .content_type("application/octet-stream")
                         .append_header((
@@ -83,18 +117,318 @@ async fn cdn_handler(
 async fn cdn_get(
     db: web::Data<Database>,
     path: web::Path<(String, String, CdnType)>,
+    query: web::Query<DownloadQuery>,
 ) -> impl Responder {
     let (slug, version, dl_type) = path.into_inner();
 
-    cdn_handler(db, slug, version, dl_type).await
+    cdn_handler(db, slug, version, dl_type, query.into_inner().game_version).await
 }
 
 #[get("/cdn/{slug}@{version}")]
 async fn cdn_get_typeless(
     db: web::Data<Database>,
     path: web::Path<(String, String)>,
+    query: web::Query<DownloadQuery>,
 ) -> impl Responder {
     let (slug, version) = path.into_inner();
     
-    cdn_handler(db, slug, version, CdnType::Package).await
+    cdn_handler(db, slug, version, CdnType::Package, query.into_inner().game_version).await
+}
+
+/// Directory holding the uploaded images of a mod or user.
+pub fn media_dir(owner: Uuid) -> PathBuf {
+    PathBuf::from(format!("./data/cdn/{}/media", owner))
+}
+
+pub fn media_url(owner: Uuid, file: &str) -> String {
+    format!(
+        "{}/cdn/media/{}/{}",
+        std::env::var("PUBLIC_URL").unwrap(),
+        owner,
+        file
+    )
+}
+
+/// Writes the files of a processed image.
+pub fn store_image(owner: Uuid, img: &ProcessedImage) -> std::io::Result<()> {
+    let dir = media_dir(owner);
+    std::fs::create_dir_all(&dir)?;
+
+    for file in &img.files {
+        std::fs::write(dir.join(&file.name), &file.data)?;
+    }
+
+    Ok(())
+}
+
+/// For kinds an owner has one of, removes the files of the images `keep` replaced. Only call
+/// this once the owner points at `keep`.
+pub fn prune_images(owner: Uuid, kind: ImageKind, keep: &str) -> std::io::Result<()> {
+    if !kind.single() {
+        return Ok(());
+    }
+
+    let dir = media_dir(owner);
+    let prefix = format!("{}-", kind.name());
+    let kept = format!("{}_", keep);
+
+    for entry in std::fs::read_dir(&dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if name.starts_with(&prefix) && !name.starts_with(&kept) {
+            std::fs::remove_file(dir.join(name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes every file generated for the image named `stem`.
+pub fn remove_image(owner: Uuid, stem: &str) -> std::io::Result<()> {
+    let dir = media_dir(owner);
+    let prefix = format!("{}_", stem);
+
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if name.starts_with(&prefix) {
+            std::fs::remove_file(dir.join(name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// An uploaded image written to the cdn.
+pub struct StoredImage {
+    /// Prefix of the generated file names, see [`remove_image`] and [`prune_images`].
+    pub stem: String,
+    /// Url of the largest variant.
+    pub url: String,
+    pub thumbnail: String,
+}
+
+/// Renders an uploaded image off the async runtime and stores it for `owner`.
+///
+/// Images it replaces are left in place, so the owner can keep pointing at them until it has
+/// been updated.
+pub async fn store_upload(
+    owner: Uuid,
+    kind: ImageKind,
+    data: Vec<u8>,
+) -> Result<StoredImage, UploadError> {
+    let stem = format!("{}-{}", kind.name(), Utc::now().timestamp_millis());
+    store_upload_as(owner, kind, stem, data).await
+}
+
+/// Like [`store_upload`], naming the generated files after `stem`.
+pub async fn store_upload_as(
+    owner: Uuid,
+    kind: ImageKind,
+    stem: String,
+    data: Vec<u8>,
+) -> Result<StoredImage, UploadError> {
+    let img = web::block({
+        let stem = stem.clone();
+        move || images::process(kind, &stem, &data)
+    })
+    .await
+    .map_err(|e| UploadError::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?
+    .map_err(UploadError::Image)?;
+
+    store_image(owner, &img).map_err(UploadError::Io)?;
+
+    Ok(StoredImage {
+        stem,
+        url: media_url(owner, &img.primary),
+        thumbnail: media_url(owner, &img.thumbnail),
+    })
+}
+
+/// Reads a request body, answering with `413 Payload Too Large` once it grows past `limit` bytes.
+pub async fn read_limited(mut payload: web::Payload, limit: usize) -> Result<Vec<u8>, HttpResponse> {
+    let mut buf = Vec::new();
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| HttpResponse::BadRequest().body(e.to_string()))?;
+        if buf.len() + chunk.len() > limit {
+            return Err(HttpResponse::PayloadTooLarge()
+                .body(format!("Upload is larger than the {} byte limit", limit)));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+
+    Ok(buf)
+}
+
+/// Uploads up to this size stay in memory while they are being received.
+const SPOOL_THRESHOLD: usize = 4 * 1024 * 1024;
+
+/// Global upload limit in bytes, configured with `MAX_UPLOAD_SIZE`.
+pub fn max_upload_size() -> usize {
+    std::env::var("MAX_UPLOAD_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(64 * 1024 * 1024)
+}
+
+/// Upload limit of `user`: their own limit when set, but never past the global one.
+pub fn upload_limit(user: &entity::users::Model) -> usize {
+    match user.upload_limit {
+        Some(limit) if limit > 0 => (limit as usize).min(max_upload_size()),
+        _ => max_upload_size(),
+    }
+}
+
+/// Rejects a request whose declared `Content-Length` is already past `limit`, before any of
+/// the body is read.
+pub fn check_content_length(req: &HttpRequest, limit: usize) -> Result<(), HttpResponse> {
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+
+    match length {
+        Some(length) if length > limit => Err(HttpResponse::PayloadTooLarge()
+            .body(format!("Upload is larger than the {} byte limit", limit))),
+        _ => Ok(()),
+    }
+}
+
+/// Like [`read_limited`], but moves the body to a temporary file once it outgrows memory.
+/// The returned file is rewound to the start.
+pub async fn spool_limited(
+    mut payload: web::Payload,
+    limit: usize,
+) -> Result<SpooledTempFile, HttpResponse> {
+    let mut file = SpooledTempFile::new(SPOOL_THRESHOLD);
+    let mut size = 0;
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| HttpResponse::BadRequest().body(e.to_string()))?;
+        size += chunk.len();
+        if size > limit {
+            return Err(HttpResponse::PayloadTooLarge()
+                .body(format!("Upload is larger than the {} byte limit", limit)));
+        }
+        file.write_all(&chunk)
+            .map_err(|e| HttpResponse::InternalServerError().body(e.to_string()))?;
+    }
+
+    file.seek(SeekFrom::Start(0))
+        .map_err(|e| HttpResponse::InternalServerError().body(e.to_string()))?;
+
+    Ok(file)
+}
+
+/// Where the forgemod of a version is stored.
+pub fn artifact_path(mod_id: Uuid, version_id: Uuid) -> PathBuf {
+    PathBuf::from(format!("./data/cdn/{}/{}.forgemod", mod_id, version_id))
+}
+
+/// Uploads are written here first, so a half written file is never served.
+pub fn staging_dir() -> PathBuf {
+    PathBuf::from("./data/cdn/.staging")
+}
+
+/// An uploaded forgemod written to the staging directory, not yet visible to downloads.
+pub struct StagedArtifact {
+    path: PathBuf,
+    /// Hex encoded sha256 of the file.
+    pub hash: String,
+}
+
+impl StagedArtifact {
+    /// Copies an upload into the staging directory, hashing it on the way.
+    ///
+    /// This does blocking io, so it should be run through `web::block`.
+    pub fn stage(mut upload: impl Read + Seek) -> std::io::Result<Self> {
+        std::fs::create_dir_all(staging_dir())?;
+        let path = staging_dir().join(format!("{}.forgemod", uuid::Uuid::new_v4()));
+
+        let staged = Self {
+            path,
+            hash: String::new(),
+        };
+        let hash = staged.write(&mut upload);
+
+        match hash {
+            Ok(hash) => Ok(Self { hash, ..staged }),
+            Err(e) => {
+                staged.discard();
+                Err(e)
+            }
+        }
+    }
+
+    fn write(&self, upload: &mut (impl Read + Seek)) -> std::io::Result<String> {
+        upload.seek(SeekFrom::Start(0))?;
+
+        let mut file = std::fs::File::create(&self.path)?;
+        let mut hasher = Sha256::new();
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let n = upload.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            file.write_all(&buf[..n])?;
+        }
+        file.sync_all()?;
+
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Moves the file to where downloads of the version read it from.
+    pub fn publish(self, mod_id: Uuid, version_id: Uuid) -> std::io::Result<()> {
+        let dest = artifact_path(mod_id, version_id);
+
+        let res = dest
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::rename(&self.path, &dest));
+        if res.is_err() {
+            self.discard();
+        }
+        res
+    }
+
+    pub fn discard(self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("failed to remove staged upload {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[get("/cdn/media/{owner}/{file}")]
+async fn cdn_media(path: web::Path<(Uuid, String)>) -> impl Responder {
+    let (owner, file) = path.into_inner();
+
+    if file.starts_with('.')
+        || !file
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let content_type = match file.rsplit('.').next() {
+        Some("png") => "image/png",
+        Some("jpg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => return HttpResponse::NotFound().finish(),
+    };
+
+    match std::fs::read(media_dir(owner).join(&file)) {
+        Ok(data) => HttpResponse::Ok()
+            .content_type(content_type)
+            .append_header(("Cache-Control", "public, max-age=31536000, immutable"))
+            .body(data),
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
 }
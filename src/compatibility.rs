@@ -0,0 +1,293 @@
+use chrono::{DateTime, Utc};
+use entity::prelude::*;
+use juniper::{graphql_value, FieldError, FieldResult, GraphQLObject};
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    FromQueryResult, IntoActiveModel, JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+    Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    jobs,
+    versions::{self, GVersion},
+};
+
+const MAX_NOTES_LEN: usize = 1000;
+
+/// Reports a game version needs before moderators can make it official.
+const MIN_PROMOTION_REPORTS: i32 = 3;
+/// Share of those reports that have to say the version works.
+const MIN_PROMOTION_SCORE: f64 = 0.75;
+
+/// Reports about one game version, summed up.
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GCompatibility {
+    pub game_version: String,
+    pub works: i32,
+    pub broken: i32,
+    /// Share of reports saying the version works, from 0 to 1.
+    pub score: f64,
+    /// Whether the game version is one the version officially supports.
+    pub official: bool,
+}
+
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GCompatibilityReport {
+    pub id: uuid::Uuid,
+    pub game_version: String,
+    pub works: bool,
+    pub notes: Option<String>,
+    /// Username of the reporter.
+    pub reporter: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Sums up the reports on a version, one entry per reported game version.
+pub async fn summarize(
+    db: &DatabaseConnection,
+    version_id: Uuid,
+) -> Result<Vec<GCompatibility>, DbErr> {
+    let reports = CompatibilityReports::find()
+        .filter(entity::compatibility_reports::Column::VersionId.eq(version_id))
+        .find_also_related(BeatSaberVersions)
+        .all(db)
+        .await?;
+
+    let official = VersionBeatSaberVersions::find()
+        .filter(entity::version_beat_saber_versions::Column::VersionId.eq(version_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|v| v.beat_saber_version_id)
+        .collect::<Vec<_>>();
+
+    let mut summary: Vec<(entity::beat_saber_versions::Model, i32, i32)> = vec![];
+    for (report, game_version) in reports {
+        let game_version = match game_version {
+            Some(game_version) => game_version,
+            None => continue,
+        };
+        let i = match summary.iter().position(|(g, _, _)| g.id == game_version.id) {
+            Some(i) => i,
+            None => {
+                summary.push((game_version, 0, 0));
+                summary.len() - 1
+            }
+        };
+        match report.works {
+            true => summary[i].1 += 1,
+            false => summary[i].2 += 1,
+        }
+    }
+
+    summary.sort_by(|(a, _, _), (b, _, _)| {
+        match (semver::Version::parse(&a.ver), semver::Version::parse(&b.ver)) {
+            (Ok(a), Ok(b)) => b.cmp(&a),
+            _ => b.ver.cmp(&a.ver),
+        }
+    });
+
+    Ok(summary
+        .into_iter()
+        .map(|(game_version, works, broken)| GCompatibility {
+            official: official.contains(&game_version.id),
+            game_version: game_version.ver,
+            works,
+            broken,
+            score: works as f64 / (works + broken) as f64,
+        })
+        .collect())
+}
+
+fn to_report(
+    r: entity::compatibility_reports::Model,
+    game_version: String,
+    reporter: String,
+) -> GCompatibilityReport {
+    GCompatibilityReport {
+        id: uuid::Uuid::from_bytes(*r.id.as_bytes()),
+        game_version,
+        works: r.works,
+        notes: r.notes,
+        reporter,
+        created_at: r.created_at.and_utc(),
+        updated_at: r.updated_at.and_utc(),
+    }
+}
+
+#[derive(FromQueryResult)]
+struct ReportRow {
+    id: Uuid,
+    works: bool,
+    notes: Option<String>,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    game_version: String,
+    reporter: String,
+}
+
+/// Records whether a version works on a game version. Reporting the same pair again replaces
+/// the earlier report.
+pub async fn submit(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    version: String,
+    game_version: String,
+    works: bool,
+    notes: Option<String>,
+) -> FieldResult<GCompatibilityReport> {
+    let (_, v) = versions::find_version(db, slug, version).await?;
+    let game_version = versions::resolve_game_version(db, &game_version).await?;
+    if game_version.deprecated {
+        return Err(FieldError::new(
+            format!("Game version `{}` has been deprecated", game_version.ver),
+            graphql_value!({ "gameVersion": "Game version has been deprecated" }),
+        ));
+    }
+
+    let notes = notes
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty());
+    if notes.as_ref().is_some_and(|n| n.chars().count() > MAX_NOTES_LEN) {
+        return Err(FieldError::new(
+            format!("Notes must be at most {} characters", MAX_NOTES_LEN),
+            graphql_value!({ "notes": "Notes are too long" }),
+        ));
+    }
+
+    let existing = CompatibilityReports::find()
+        .filter(entity::compatibility_reports::Column::VersionId.eq(v.id))
+        .filter(entity::compatibility_reports::Column::BeatSaberVersionId.eq(game_version.id))
+        .filter(entity::compatibility_reports::Column::UserId.eq(user.id))
+        .one(db)
+        .await?;
+
+    let report = match existing {
+        Some(existing) => {
+            let mut am = existing.into_active_model();
+            am.works = Set(works);
+            am.notes = Set(notes);
+            am.updated_at = Set(Utc::now().naive_utc());
+            am.update(db).await?
+        }
+        None => {
+            entity::compatibility_reports::ActiveModel {
+                version_id: Set(v.id),
+                beat_saber_version_id: Set(game_version.id),
+                user_id: Set(user.id),
+                works: Set(works),
+                notes: Set(notes),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?
+        }
+    };
+
+    Ok(to_report(report, game_version.ver, user.username.clone()))
+}
+
+/// Lists the reports on a version, newest first.
+pub async fn find_reports(
+    db: &DatabaseConnection,
+    slug: String,
+    version: String,
+    game_version: Option<String>,
+) -> FieldResult<Vec<GCompatibilityReport>> {
+    let (_, v) = versions::find_version(db, slug, version).await?;
+
+    let mut query = CompatibilityReports::find()
+        .filter(entity::compatibility_reports::Column::VersionId.eq(v.id));
+    if let Some(game_version) = game_version {
+        let game_version = versions::resolve_game_version(db, &game_version).await?;
+        query = query
+            .filter(entity::compatibility_reports::Column::BeatSaberVersionId.eq(game_version.id));
+    }
+
+    let reports = query
+        .join(
+            JoinType::InnerJoin,
+            entity::compatibility_reports::Relation::BeatSaberVersions.def(),
+        )
+        .join(JoinType::InnerJoin, entity::compatibility_reports::Relation::Users.def())
+        .column_as(entity::beat_saber_versions::Column::Ver, "game_version")
+        .column_as(entity::users::Column::Username, "reporter")
+        .order_by_desc(entity::compatibility_reports::Column::UpdatedAt)
+        .into_model::<ReportRow>()
+        .all(db)
+        .await?;
+
+    Ok(reports
+        .into_iter()
+        .map(|r| GCompatibilityReport {
+            id: uuid::Uuid::from_bytes(*r.id.as_bytes()),
+            game_version: r.game_version,
+            works: r.works,
+            notes: r.notes,
+            reporter: r.reporter,
+            created_at: r.created_at.and_utc(),
+            updated_at: r.updated_at.and_utc(),
+        })
+        .collect())
+}
+
+/// Makes a game version players vouch for an officially supported one of the version.
+pub async fn promote(
+    db: &DatabaseConnection,
+    slug: String,
+    version: String,
+    game_version: String,
+) -> FieldResult<GVersion> {
+    let (m, v) = versions::find_version(db, slug, version).await?;
+    let game_version = versions::resolve_game_version(db, &game_version).await?;
+    if game_version.deprecated {
+        return Err(FieldError::new(
+            format!("Game version `{}` has been deprecated", game_version.ver),
+            graphql_value!({ "gameVersion": "Game version has been deprecated" }),
+        ));
+    }
+
+    let summary = summarize(db, v.id)
+        .await?
+        .into_iter()
+        .find(|c| c.game_version == game_version.ver);
+
+    match summary {
+        Some(c) if c.official => {
+            return Err(FieldError::new(
+                "The game version is already supported",
+                graphql_value!({ "gameVersion": "The game version is already supported" }),
+            ))
+        }
+        Some(c) if c.works + c.broken >= MIN_PROMOTION_REPORTS && c.score >= MIN_PROMOTION_SCORE => {}
+        _ => {
+            return Err(FieldError::new(
+                format!(
+                    "At least {} reports are needed, {}% of them saying the version works",
+                    MIN_PROMOTION_REPORTS,
+                    MIN_PROMOTION_SCORE * 100.0
+                ),
+                graphql_value!({ "gameVersion": "Not enough reports say the version works" }),
+            ))
+        }
+    }
+
+    let trans = db.begin().await?;
+
+    entity::version_beat_saber_versions::ActiveModel {
+        version_id: Set(v.id),
+        beat_saber_version_id: Set(game_version.id),
+    }
+    .insert(&trans)
+    .await?;
+
+    versions::sync_mod_game_versions(&trans, m.id).await?;
+    jobs::enqueue(&trans, jobs::Job::IndexMod { mod_id: m.id }).await?;
+
+    trans.commit().await?;
+
+    GVersion::from_db_version(db, v).await
+}
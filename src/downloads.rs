@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use juniper::{graphql_value, FieldError, FieldResult, GraphQLEnum, GraphQLObject};
+use sea_orm::{
+    prelude::Uuid, ConnectionTrait, DatabaseConnection, DbErr, FromQueryResult, Statement,
+    TransactionTrait,
+};
+
+/// Days covered when a history query leaves out `from`.
+const DEFAULT_DAYS: i64 = 30;
+/// Longest span a history query may cover.
+const MAX_DAYS: i64 = 3 * 366;
+
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Granularity {
+    Day,
+    /// Weeks start on Monday.
+    Week,
+    Month,
+}
+
+impl Granularity {
+    /// Name of the period, as `date_trunc` and the rollups' `granularity` spell it.
+    fn field(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+
+    /// First day of the period `date` is in.
+    fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Self::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// Last day of the period `date` is in.
+    fn end(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => self.start(date) + Duration::days(6),
+            Self::Month => {
+                let next = match date.month() {
+                    12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+                    m => NaiveDate::from_ymd_opt(date.year(), m + 1, 1),
+                };
+                next.unwrap() - Duration::days(1)
+            }
+        }
+    }
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct GDownloadPoint {
+    /// First day of the period.
+    pub date: NaiveDate,
+    pub downloads: i32,
+}
+
+/// Downloads of one version or game version over time.
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct GDownloadSeries {
+    /// The version, or the game version. `None` for downloads by clients that didn't say which
+    /// game version they run.
+    pub key: Option<String>,
+    pub points: Vec<GDownloadPoint>,
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct GDownloadHistory {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub granularity: Granularity,
+    /// Every download of the mod. Periods without downloads are left out.
+    pub total: Vec<GDownloadPoint>,
+    pub by_version: Vec<GDownloadSeries>,
+    pub by_game_version: Vec<GDownloadSeries>,
+}
+
+/// Counts a download of a version, for the game version the client runs if it said.
+///
+/// The version's and the mod's totals and the week's and month's rollups are bumped in the same
+/// transaction as the day's bucket, so the history always adds up to them.
+pub async fn record(
+    db: &DatabaseConnection,
+    version: &entity::versions::Model,
+    game_version: Option<Uuid>,
+) -> Result<(), DbErr> {
+    let trans = db.begin().await?;
+    let backend = trans.get_database_backend();
+
+    trans
+        .execute(Statement::from_sql_and_values(
+            backend,
+            r#"INSERT INTO version_downloads (version_id, beat_saber_version_id, day, downloads)
+               VALUES ($1, $2, CURRENT_DATE, 1)
+               ON CONFLICT (
+                   version_id,
+                   COALESCE(beat_saber_version_id, '00000000-0000-0000-0000-000000000000'::uuid),
+                   day
+               )
+               DO UPDATE SET downloads = version_downloads.downloads + 1"#,
+            [version.id.into(), game_version.into()],
+        ))
+        .await?;
+
+    trans
+        .execute(Statement::from_sql_and_values(
+            backend,
+            r#"INSERT INTO version_download_rollups
+                   (version_id, beat_saber_version_id, granularity, period, downloads)
+               VALUES ($1, $2, 'week', date_trunc('week', CURRENT_DATE)::date, 1),
+                      ($1, $2, 'month', date_trunc('month', CURRENT_DATE)::date, 1)
+               ON CONFLICT (
+                   version_id,
+                   COALESCE(beat_saber_version_id, '00000000-0000-0000-0000-000000000000'::uuid),
+                   granularity,
+                   period
+               )
+               DO UPDATE SET downloads = version_download_rollups.downloads + 1"#,
+            [version.id.into(), game_version.into()],
+        ))
+        .await?;
+
+    trans
+        .execute(Statement::from_sql_and_values(
+            backend,
+            "UPDATE version_stats SET downloads = downloads + 1 WHERE id = $1",
+            [version.stats.into()],
+        ))
+        .await?;
+
+    trans
+        .execute(Statement::from_sql_and_values(
+            backend,
+            r#"UPDATE mod_stats SET downloads = downloads + 1
+               WHERE id = (SELECT stats FROM mods WHERE id = $1)"#,
+            [version.mod_id.into()],
+        ))
+        .await?;
+
+    trans.commit().await
+}
+
+#[derive(FromQueryResult)]
+struct Bucket {
+    period: NaiveDate,
+    version: String,
+    game_version: Option<String>,
+    downloads: i64,
+}
+
+fn to_points(points: BTreeMap<NaiveDate, i64>) -> Vec<GDownloadPoint> {
+    points
+        .into_iter()
+        .map(|(date, downloads)| GDownloadPoint {
+            date,
+            downloads: downloads.min(i32::MAX as i64) as i32,
+        })
+        .collect()
+}
+
+fn to_series(series: BTreeMap<Option<String>, BTreeMap<NaiveDate, i64>>) -> Vec<GDownloadSeries> {
+    series
+        .into_iter()
+        .map(|(key, points)| GDownloadSeries {
+            key,
+            points: to_points(points),
+        })
+        .collect()
+}
+
+/// Downloads of a mod between `from` and `to`, both included, summed per period. Weeks and
+/// months are read from the rollups, so `from` and `to` are widened to whole periods.
+pub async fn history(
+    db: &DatabaseConnection,
+    mod_id: Uuid,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    granularity: Granularity,
+) -> FieldResult<GDownloadHistory> {
+    let to = to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = from.unwrap_or(to - Duration::days(DEFAULT_DAYS - 1));
+    if from > to {
+        return Err(FieldError::new(
+            "`from` must not be after `to`",
+            graphql_value!({ "from": "`from` is after `to`" }),
+        ));
+    }
+    if (to - from).num_days() >= MAX_DAYS {
+        return Err(FieldError::new(
+            format!("At most {} days can be queried at once", MAX_DAYS),
+            graphql_value!({ "from": "The range is too long" }),
+        ));
+    }
+
+    let from = granularity.start(from);
+    let to = granularity.end(to);
+
+    let sql = match granularity {
+        Granularity::Day => {
+            r#"SELECT version_downloads.day AS period,
+                      versions.version AS version,
+                      beat_saber_versions.ver AS game_version,
+                      SUM(version_downloads.downloads)::bigint AS downloads
+               FROM version_downloads
+               JOIN versions ON versions.id = version_downloads.version_id
+               LEFT JOIN beat_saber_versions
+                   ON beat_saber_versions.id = version_downloads.beat_saber_version_id
+               WHERE versions.mod_id = $1
+                 AND version_downloads.day BETWEEN $2 AND $3
+               GROUP BY 1, 2, 3"#
+        }
+        Granularity::Week | Granularity::Month => {
+            r#"SELECT version_download_rollups.period AS period,
+                      versions.version AS version,
+                      beat_saber_versions.ver AS game_version,
+                      SUM(version_download_rollups.downloads)::bigint AS downloads
+               FROM version_download_rollups
+               JOIN versions ON versions.id = version_download_rollups.version_id
+               LEFT JOIN beat_saber_versions
+                   ON beat_saber_versions.id = version_download_rollups.beat_saber_version_id
+               WHERE versions.mod_id = $1
+                 AND version_download_rollups.period BETWEEN $2 AND $3
+                 AND version_download_rollups.granularity = $4
+               GROUP BY 1, 2, 3"#
+        }
+    };
+
+    let mut values = vec![mod_id.into(), from.into(), to.into()];
+    if granularity != Granularity::Day {
+        values.push(granularity.field().into());
+    }
+
+    let buckets = Bucket::find_by_statement(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        sql,
+        values,
+    ))
+    .all(db)
+    .await?;
+
+    let mut total = BTreeMap::new();
+    let mut by_version = BTreeMap::new();
+    let mut by_game_version = BTreeMap::new();
+    for b in buckets {
+        *total.entry(b.period).or_insert(0) += b.downloads;
+        *by_version
+            .entry(Some(b.version))
+            .or_insert_with(BTreeMap::new)
+            .entry(b.period)
+            .or_insert(0) += b.downloads;
+        *by_game_version
+            .entry(b.game_version)
+            .or_insert_with(BTreeMap::new)
+            .entry(b.period)
+            .or_insert(0) += b.downloads;
+    }
+
+    Ok(GDownloadHistory {
+        from,
+        to,
+        granularity,
+        total: to_points(total),
+        by_version: to_series(by_version),
+        by_game_version: to_series(by_game_version),
+    })
+}
@@ -0,0 +1,454 @@
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use entity::prelude::*;
+use juniper::{graphql_value, FieldError, FieldResult, GraphQLEnum, GraphQLObject};
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DatabaseTransaction, DbErr, EntityTrait, IntoActiveModel, ModelTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{can_edit_mod, get_user_from_request},
+    cdn,
+    images::{self, ImageKind, UploadError},
+    Database,
+};
+
+/// Most images and videos a mod's gallery may hold.
+const MAX_GALLERY_ITEMS: u64 = 20;
+const MAX_CAPTION_LEN: usize = 200;
+
+/// Sites video links may point to, so the website and mod managers know how to embed them.
+const VIDEO_HOSTS: &[&str] = &[
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "youtu.be",
+    "vimeo.com",
+    "player.vimeo.com",
+    "streamable.com",
+];
+
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    /// A screenshot stored on the cdn.
+    Image,
+    /// A link to a video on one of the supported sites.
+    Video,
+}
+
+impl MediaKind {
+    /// The name stored in `mod_media.kind`.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::Video => "video",
+        }
+    }
+
+    fn from_name(name: &str) -> Self {
+        match name {
+            "video" => Self::Video,
+            _ => Self::Image,
+        }
+    }
+}
+
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GMedia {
+    pub id: uuid::Uuid,
+    pub kind: MediaKind,
+    pub url: String,
+    /// WebP thumbnail of images. Videos have none.
+    pub thumbnail: Option<String>,
+    pub caption: Option<String>,
+    /// Place in the gallery, starting at 0.
+    pub position: i32,
+}
+
+impl From<entity::mod_media::Model> for GMedia {
+    fn from(m: entity::mod_media::Model) -> Self {
+        GMedia {
+            id: uuid::Uuid::from_bytes(*m.id.as_bytes()),
+            kind: MediaKind::from_name(&m.kind),
+            url: m.url,
+            thumbnail: m.thumbnail,
+            caption: m.caption,
+            position: m.position,
+        }
+    }
+}
+
+/// The gallery of a mod, in order.
+pub async fn find_by_mod(db: &DatabaseConnection, mod_id: Uuid) -> Result<Vec<GMedia>, DbErr> {
+    Ok(ModMedia::find()
+        .filter(entity::mod_media::Column::ModId.eq(mod_id))
+        .order_by_asc(entity::mod_media::Column::Position)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(GMedia::from)
+        .collect())
+}
+
+/// File names of a screenshot's variants start with this.
+fn screenshot_stem(id: Uuid) -> String {
+    format!("{}-{}", ImageKind::Screenshot.name(), id)
+}
+
+fn clean_caption(caption: Option<String>) -> Result<Option<String>, String> {
+    let caption = caption
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+    if caption.as_ref().is_some_and(|c| c.chars().count() > MAX_CAPTION_LEN) {
+        return Err(format!(
+            "Captions must be at most {} characters",
+            MAX_CAPTION_LEN
+        ));
+    }
+    Ok(caption)
+}
+
+fn clean_video_url(url: &str) -> Result<String, String> {
+    match url::Url::parse(url.trim()) {
+        Ok(url)
+            if url.scheme() == "https"
+                && url.host_str().is_some_and(|h| VIDEO_HOSTS.contains(&h)) =>
+        {
+            Ok(url.to_string())
+        }
+        _ => Err(format!(
+            "Videos must be https links to one of {}",
+            VIDEO_HOSTS.join(", ")
+        )),
+    }
+}
+
+async fn find_editable_mod(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+) -> FieldResult<entity::mods::Model> {
+    let m = Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Mod not found",
+                graphql_value!({ "internal_error": "Mod not found" }),
+            )
+        })?;
+
+    if !can_edit_mod(user, &m).await {
+        return Err(FieldError::new(
+            "Unauthorized",
+            graphql_value!({ "auth": "Unauthorized" }),
+        ));
+    }
+
+    Ok(m)
+}
+
+async fn find_item<C: ConnectionTrait>(
+    db: &C,
+    m: &entity::mods::Model,
+    id: uuid::Uuid,
+) -> FieldResult<entity::mod_media::Model> {
+    ModMedia::find_by_id(Uuid::from_bytes(*id.as_bytes()))
+        .filter(entity::mod_media::Column::ModId.eq(m.id))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Gallery item not found",
+                graphql_value!({ "notFound": "Gallery item not found" }),
+            )
+        })
+}
+
+/// Locks the mod's row until `trans` ends, so changes to the gallery of one mod are made one
+/// after another and can't hand out the same position twice.
+async fn lock_gallery(trans: &DatabaseTransaction, mod_id: Uuid) -> Result<(), DbErr> {
+    Mods::find_by_id(mod_id)
+        .lock_exclusive()
+        .one(trans)
+        .await
+        .map(|_| ())
+}
+
+async fn gallery_size<C: ConnectionTrait>(db: &C, mod_id: Uuid) -> Result<u64, DbErr> {
+    ModMedia::find()
+        .filter(entity::mod_media::Column::ModId.eq(mod_id))
+        .count(db)
+        .await
+}
+
+/// Adds `item` to the end of a mod's gallery, or returns `None` if the gallery is full.
+async fn append(
+    db: &DatabaseConnection,
+    mod_id: Uuid,
+    mut item: entity::mod_media::ActiveModel,
+) -> Result<Option<entity::mod_media::Model>, DbErr> {
+    let trans = db.begin().await?;
+    lock_gallery(&trans, mod_id).await?;
+
+    let count = gallery_size(&trans, mod_id).await?;
+    if count >= MAX_GALLERY_ITEMS {
+        return Ok(None);
+    }
+
+    item.mod_id = Set(mod_id);
+    item.position = Set(count as i32);
+    let item = item.insert(&trans).await?;
+
+    trans.commit().await?;
+
+    Ok(Some(item))
+}
+
+#[derive(Deserialize)]
+pub struct ScreenshotQuery {
+    pub caption: Option<String>,
+}
+
+/// Adds a screenshot to the end of a mod's gallery. The body is the image, the caption is
+/// passed in the query string.
+#[post("/mods/{slug}/gallery")]
+pub async fn upload_screenshot(
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    query: web::Query<ScreenshotQuery>,
+    payload: web::Payload,
+    req: HttpRequest,
+) -> impl Responder {
+    let user = match get_user_from_request(&req, &db.pool).await {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body("Unauthorized"),
+    };
+
+    let db_mod = match Mods::find()
+        .filter(entity::mods::Column::Slug.eq(path.into_inner()))
+        .one(&db.pool)
+        .await
+    {
+        Ok(Some(db_mod)) => db_mod,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            log::error!("failed to look up mod: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    if !can_edit_mod(&user, &db_mod).await {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let caption = match clean_caption(query.into_inner().caption) {
+        Ok(caption) => caption,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    // checked again once the screenshot is stored, this only spares processing it
+    let count = match gallery_size(&db.pool, db_mod.id).await {
+        Ok(count) => count,
+        Err(e) => {
+            log::error!("failed to count gallery of {}: {}", db_mod.id, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if count >= MAX_GALLERY_ITEMS {
+        return HttpResponse::BadRequest().body(format!(
+            "A gallery can hold at most {} items",
+            MAX_GALLERY_ITEMS
+        ));
+    }
+
+    let buf = match cdn::read_limited(payload, images::MAX_IMAGE_SIZE).await {
+        Ok(buf) => buf,
+        Err(res) => return res,
+    };
+
+    let id = Uuid::from_bytes(*uuid::Uuid::new_v4().as_bytes());
+    let img = match cdn::store_upload_as(
+        db_mod.id,
+        ImageKind::Screenshot,
+        screenshot_stem(id),
+        buf,
+    )
+    .await
+    {
+        Ok(img) => img,
+        Err(UploadError::Image(e)) => return HttpResponse::BadRequest().body(e.to_string()),
+        Err(e) => {
+            log::error!("failed to store screenshot: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let item = entity::mod_media::ActiveModel {
+        id: Set(id),
+        kind: Set(MediaKind::Image.name().to_string()),
+        url: Set(img.url),
+        thumbnail: Set(Some(img.thumbnail)),
+        caption: Set(caption),
+        ..Default::default()
+    };
+
+    let res = match append(&db.pool, db_mod.id, item).await {
+        Ok(Some(item)) => return HttpResponse::Created().json(GMedia::from(item)),
+        Ok(None) => HttpResponse::BadRequest().body(format!(
+            "A gallery can hold at most {} items",
+            MAX_GALLERY_ITEMS
+        )),
+        Err(e) => {
+            log::error!("failed to add screenshot to {}: {}", db_mod.id, e);
+            HttpResponse::InternalServerError().finish()
+        }
+    };
+
+    if let Err(e) = cdn::remove_image(db_mod.id, &screenshot_stem(id)) {
+        log::warn!("failed to clean up screenshot {}: {}", id, e);
+    }
+    res
+}
+
+/// Adds a video link to the end of a mod's gallery.
+pub async fn add_video(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    url: String,
+    caption: Option<String>,
+) -> FieldResult<GMedia> {
+    let m = find_editable_mod(db, user, slug).await?;
+
+    let url = clean_video_url(&url)
+        .map_err(|e| FieldError::new(e, graphql_value!({ "url": "Unsupported video link" })))?;
+    let caption = clean_caption(caption)
+        .map_err(|e| FieldError::new(e, graphql_value!({ "caption": "Caption is too long" })))?;
+
+    let item = entity::mod_media::ActiveModel {
+        kind: Set(MediaKind::Video.name().to_string()),
+        url: Set(url),
+        caption: Set(caption),
+        ..Default::default()
+    };
+
+    match append(db, m.id, item).await? {
+        Some(item) => Ok(item.into()),
+        None => Err(FieldError::new(
+            format!("A gallery can hold at most {} items", MAX_GALLERY_ITEMS),
+            graphql_value!({ "gallery": "The gallery is full" }),
+        )),
+    }
+}
+
+pub async fn set_caption(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    id: uuid::Uuid,
+    caption: Option<String>,
+) -> FieldResult<GMedia> {
+    let m = find_editable_mod(db, user, slug).await?;
+    let item = find_item(db, &m, id).await?;
+
+    let caption = clean_caption(caption)
+        .map_err(|e| FieldError::new(e, graphql_value!({ "caption": "Caption is too long" })))?;
+
+    let mut am = item.into_active_model();
+    am.caption = Set(caption);
+
+    Ok(am.update(db).await?.into())
+}
+
+/// Puts the gallery in the order of `ids`, which has to name every item exactly once.
+pub async fn reorder(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    ids: Vec<uuid::Uuid>,
+) -> FieldResult<Vec<GMedia>> {
+    let m = find_editable_mod(db, user, slug).await?;
+
+    let trans = db.begin().await?;
+    lock_gallery(&trans, m.id).await?;
+
+    let items = ModMedia::find()
+        .filter(entity::mod_media::Column::ModId.eq(m.id))
+        .all(&trans)
+        .await?;
+
+    let ids = ids
+        .iter()
+        .map(|id| Uuid::from_bytes(*id.as_bytes()))
+        .collect::<Vec<_>>();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    sorted.dedup();
+    if sorted.len() != ids.len()
+        || ids.len() != items.len()
+        || !items.iter().all(|i| ids.contains(&i.id))
+    {
+        return Err(FieldError::new(
+            "The new order has to list every gallery item once",
+            graphql_value!({ "ids": "Every gallery item has to be listed once" }),
+        ));
+    }
+
+    for item in items {
+        let position = ids.iter().position(|id| *id == item.id).unwrap_or_default() as i32;
+        if item.position != position {
+            let mut am = item.into_active_model();
+            am.position = Set(position);
+            am.update(&trans).await?;
+        }
+    }
+    trans.commit().await?;
+
+    Ok(find_by_mod(db, m.id).await?)
+}
+
+/// Removes an item from the gallery, deleting the files of screenshots.
+pub async fn remove(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    id: uuid::Uuid,
+) -> FieldResult<Vec<GMedia>> {
+    let m = find_editable_mod(db, user, slug).await?;
+
+    let trans = db.begin().await?;
+    lock_gallery(&trans, m.id).await?;
+
+    let item = find_item(&trans, &m, id).await?;
+
+    let (item_id, kind, position) = (item.id, MediaKind::from_name(&item.kind), item.position);
+    item.delete(&trans).await?;
+
+    // close the gap the item leaves
+    for later in ModMedia::find()
+        .filter(entity::mod_media::Column::ModId.eq(m.id))
+        .filter(entity::mod_media::Column::Position.gt(position))
+        .all(&trans)
+        .await?
+    {
+        let position = later.position;
+        let mut am = later.into_active_model();
+        am.position = Set(position - 1);
+        am.update(&trans).await?;
+    }
+
+    trans.commit().await?;
+
+    if kind == MediaKind::Image {
+        if let Err(e) = cdn::remove_image(m.id, &screenshot_stem(item_id)) {
+            log::warn!("failed to remove files of screenshot {}: {}", item_id, e);
+        }
+    }
+
+    Ok(find_by_mod(db, m.id).await?)
+}
@@ -0,0 +1,260 @@
+use chrono::NaiveDate;
+use entity::prelude::*;
+use juniper::{graphql_value, FieldError, FieldResult, GraphQLObject};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    IntoActiveModel, QueryFilter, Set, TransactionTrait,
+};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GBeatSaberVersion {
+    pub id: Uuid,
+    pub ver: String,
+    pub aliases: Vec<String>,
+    pub release_date: Option<NaiveDate>,
+    pub deprecated: bool,
+    pub is_latest: bool,
+}
+
+impl GBeatSaberVersion {
+    async fn from_db_version(
+        db: &DatabaseConnection,
+        v: entity::beat_saber_versions::Model,
+    ) -> Result<Self, FieldError> {
+        let aliases = BeatSaberVersionAliases::find()
+            .filter(entity::beat_saber_version_aliases::Column::BeatSaberVersionId.eq(v.id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|a| a.alias)
+            .collect::<Vec<_>>();
+
+        Ok(GBeatSaberVersion {
+            id: Uuid::from_bytes(*v.id.as_bytes()),
+            ver: v.ver,
+            aliases,
+            release_date: v.release_date,
+            deprecated: v.deprecated,
+            is_latest: v.is_latest,
+        })
+    }
+}
+
+fn not_found() -> FieldError {
+    FieldError::new(
+        "Unknown game version",
+        graphql_value!({ "version": "Unknown game version" }),
+    )
+}
+
+/// Looks up a game version by its version string or one of its aliases.
+pub async fn resolve<C: ConnectionTrait>(
+    db: &C,
+    ver: &str,
+) -> Result<Option<entity::beat_saber_versions::Model>, DbErr> {
+    let by_ver = BeatSaberVersions::find()
+        .filter(entity::beat_saber_versions::Column::Ver.eq(ver))
+        .one(db)
+        .await?;
+
+    if by_ver.is_some() {
+        return Ok(by_ver);
+    }
+
+    match BeatSaberVersionAliases::find()
+        .filter(entity::beat_saber_version_aliases::Column::Alias.eq(ver))
+        .one(db)
+        .await?
+    {
+        Some(alias) => BeatSaberVersions::find_by_id(alias.beat_saber_version_id)
+            .one(db)
+            .await,
+        None => Ok(None),
+    }
+}
+
+pub async fn find_all(
+    db: &DatabaseConnection,
+    include_deprecated: bool,
+) -> FieldResult<Vec<GBeatSaberVersion>> {
+    let mut query = BeatSaberVersions::find();
+    if !include_deprecated {
+        query = query.filter(entity::beat_saber_versions::Column::Deprecated.eq(false));
+    }
+
+    let mut vers = query.all(db).await?;
+    vers.sort_by(|a, b| match (Version::parse(&a.ver), Version::parse(&b.ver)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.ver.cmp(&b.ver),
+    });
+
+    let mut r = vec![];
+    for v in vers {
+        r.push(GBeatSaberVersion::from_db_version(db, v).await?);
+    }
+    Ok(r)
+}
+
+pub async fn create(
+    db: &DatabaseConnection,
+    ver: String,
+    release_date: Option<NaiveDate>,
+    is_latest: bool,
+) -> FieldResult<GBeatSaberVersion> {
+    if Version::parse(&ver).is_err() {
+        return Err(FieldError::new(
+            "Game version must be valid semver",
+            graphql_value!({ "version": "Game version must be valid semver" }),
+        ));
+    }
+
+    if resolve(db, &ver).await?.is_some() {
+        return Err(FieldError::new(
+            "Game version already exists",
+            graphql_value!({ "version": "Game version already exists" }),
+        ));
+    }
+
+    let trans = db.begin().await?;
+
+    if is_latest {
+        clear_latest(&trans).await?;
+    }
+
+    let v = entity::beat_saber_versions::ActiveModel {
+        ver: Set(ver),
+        release_date: Set(release_date),
+        deprecated: Set(false),
+        is_latest: Set(is_latest),
+        ..Default::default()
+    }
+    .insert(&trans)
+    .await?;
+
+    trans.commit().await?;
+
+    GBeatSaberVersion::from_db_version(db, v).await
+}
+
+pub async fn set_deprecated(
+    db: &DatabaseConnection,
+    ver: String,
+    deprecated: bool,
+) -> FieldResult<GBeatSaberVersion> {
+    let v = resolve(db, &ver).await?.ok_or_else(not_found)?;
+
+    let mut am = v.into_active_model();
+    am.deprecated = Set(deprecated);
+    // a deprecated version can't be the one new uploads should target
+    if deprecated {
+        am.is_latest = Set(false);
+    }
+    let v = am.update(db).await?;
+
+    GBeatSaberVersion::from_db_version(db, v).await
+}
+
+pub async fn set_latest(db: &DatabaseConnection, ver: String) -> FieldResult<GBeatSaberVersion> {
+    let v = resolve(db, &ver).await?.ok_or_else(not_found)?;
+
+    if v.deprecated {
+        return Err(FieldError::new(
+            "A deprecated game version can not be marked as latest",
+            graphql_value!({ "version": "A deprecated game version can not be marked as latest" }),
+        ));
+    }
+
+    let trans = db.begin().await?;
+    clear_latest(&trans).await?;
+
+    let mut am = v.into_active_model();
+    am.is_latest = Set(true);
+    let v = am.update(&trans).await?;
+
+    trans.commit().await?;
+
+    GBeatSaberVersion::from_db_version(db, v).await
+}
+
+pub async fn set_release_date(
+    db: &DatabaseConnection,
+    ver: String,
+    release_date: Option<NaiveDate>,
+) -> FieldResult<GBeatSaberVersion> {
+    let v = resolve(db, &ver).await?.ok_or_else(not_found)?;
+
+    let mut am = v.into_active_model();
+    am.release_date = Set(release_date);
+    let v = am.update(db).await?;
+
+    GBeatSaberVersion::from_db_version(db, v).await
+}
+
+pub async fn add_alias(
+    db: &DatabaseConnection,
+    ver: String,
+    alias: String,
+) -> FieldResult<GBeatSaberVersion> {
+    let v = resolve(db, &ver).await?.ok_or_else(not_found)?;
+
+    if alias.trim().is_empty() {
+        return Err(FieldError::new(
+            "Alias can not be empty",
+            graphql_value!({ "alias": "Alias can not be empty" }),
+        ));
+    }
+
+    if resolve(db, &alias).await?.is_some() {
+        return Err(FieldError::new(
+            "Alias is already in use",
+            graphql_value!({ "alias": "Alias is already in use" }),
+        ));
+    }
+
+    entity::beat_saber_version_aliases::ActiveModel {
+        beat_saber_version_id: Set(v.id),
+        alias: Set(alias),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    GBeatSaberVersion::from_db_version(db, v).await
+}
+
+pub async fn remove_alias(db: &DatabaseConnection, alias: String) -> FieldResult<GBeatSaberVersion> {
+    let a = BeatSaberVersionAliases::find()
+        .filter(entity::beat_saber_version_aliases::Column::Alias.eq(alias))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Unknown alias",
+                graphql_value!({ "alias": "Unknown alias" }),
+            )
+        })?;
+
+    let v = BeatSaberVersions::find_by_id(a.beat_saber_version_id)
+        .one(db)
+        .await?
+        .ok_or_else(not_found)?;
+
+    BeatSaberVersionAliases::delete_by_id(a.id).exec(db).await?;
+
+    GBeatSaberVersion::from_db_version(db, v).await
+}
+
+async fn clear_latest<C: ConnectionTrait>(db: &C) -> Result<(), DbErr> {
+    BeatSaberVersions::update_many()
+        .col_expr(
+            entity::beat_saber_versions::Column::IsLatest,
+            sea_orm::sea_query::Expr::value(false),
+        )
+        .filter(entity::beat_saber_versions::Column::IsLatest.eq(true))
+        .exec(db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,406 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use entity::prelude::*;
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    IntoActiveModel, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    auth::{
+        get_user_from_request, validate_permissions, JWTAuth, Permission, DEFAULT_PERMISSIONS,
+    },
+    jobs,
+    oauth::{self, Identity, OAuthError, Provider},
+    users::ProfileOverrides,
+    Database, KEY,
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct AuthorizeReq {
+    pub redirect_uri: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UserAuthReq {
+    pub code: String,
+    pub state: String,
+}
+
+fn provider(name: &str) -> Result<Provider, OAuthError> {
+    Provider::from_name(name).ok_or_else(|| OAuthError::UnknownProvider(name.to_string()))
+}
+
+/// `users.github_id` mirrors the linked GitHub account.
+fn github_id(identity: &Identity) -> Option<i64> {
+    match identity.provider {
+        Provider::Github => identity.provider_user_id.parse::<i64>().ok(),
+        _ => None,
+    }
+}
+
+async fn find_identity(
+    db: &DatabaseConnection,
+    provider: Provider,
+    provider_user_id: &str,
+) -> Result<Option<entity::user_identities::Model>, DbErr> {
+    UserIdentities::find()
+        .filter(entity::user_identities::Column::Provider.eq(provider.name()))
+        .filter(entity::user_identities::Column::ProviderUserId.eq(provider_user_id))
+        .one(db)
+        .await
+}
+
+async fn identities_of(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+) -> Result<Vec<entity::user_identities::Model>, DbErr> {
+    UserIdentities::find()
+        .filter(entity::user_identities::Column::UserId.eq(user_id))
+        .order_by_asc(entity::user_identities::Column::CreatedAt)
+        .all(db)
+        .await
+}
+
+/// Usernames tried for an account before giving up.
+const MAX_USERNAME_CANDIDATES: usize = 100;
+
+/// Picks the username for an account, falling back to `{username}-{provider user id}` and then
+/// numbered variants of it while other accounts hold the name.
+async fn resolve_username(
+    db: &DatabaseConnection,
+    identity: &Identity,
+    own_id: Option<Uuid>,
+) -> Result<String, OAuthError> {
+    let fallback = format!("{}-{}", identity.username, identity.provider_user_id);
+    let candidates = [identity.username.clone(), fallback.clone()]
+        .into_iter()
+        .chain((2..).map(|n| format!("{}-{}", fallback, n)))
+        .take(MAX_USERNAME_CANDIDATES)
+        .collect::<Vec<_>>();
+
+    let taken = Users::find()
+        .filter(entity::users::Column::Username.is_in(candidates.iter().map(String::as_str)))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|u| Some(u.id) != own_id)
+        .map(|u| u.username)
+        .collect::<Vec<_>>();
+
+    candidates
+        .into_iter()
+        .find(|c| !taken.contains(c))
+        .ok_or(OAuthError::NoUsername(identity.provider))
+}
+
+/// Logs a user in through `identity`, creating the account on first login.
+///
+/// Logins through the identity an account was created with refresh the profile fields the
+/// user has not overridden.
+pub async fn login(
+    db: &DatabaseConnection,
+    identity: Identity,
+) -> Result<entity::users::Model, OAuthError> {
+    let now = Utc::now().naive_utc();
+
+    let linked = match find_identity(db, identity.provider, &identity.provider_user_id).await? {
+        Some(linked) => linked,
+        None => return create_account(db, identity).await,
+    };
+
+    let user = Users::find_by_id(linked.user_id)
+        .one(db)
+        .await?
+        .ok_or(OAuthError::Unauthorized)?;
+
+    let mut am = user.clone().into_active_model();
+    let mut renamed = false;
+
+    if linked.is_primary {
+        let overrides = ProfileOverrides::from_bits_truncate(user.profile_overrides);
+        let username = resolve_username(db, &identity, Some(user.id)).await?;
+
+        renamed = username != user.username;
+        if renamed {
+            am.username = Set(username);
+        }
+        if identity.provider.has_bio()
+            && !overrides.contains(ProfileOverrides::BIO)
+            && identity.bio != user.bio
+        {
+            am.bio = Set(identity.bio);
+        }
+        if !overrides.contains(ProfileOverrides::AVATAR) && identity.avatar_url != user.avatar {
+            am.avatar = Set(identity.avatar_url);
+        }
+        if am.is_changed() {
+            am.updated_at = Set(now);
+        }
+    }
+    am.last_login_at = Set(Some(now));
+
+    let trans = db.begin().await?;
+    let user = am.update(&trans).await?;
+
+    // search documents show the author's username
+    if renamed {
+        let mods = Mods::find()
+            .filter(entity::mods::Column::Author.eq(user.id))
+            .all(&trans)
+            .await?;
+        for m in mods {
+            jobs::enqueue(&trans, jobs::Job::IndexMod { mod_id: m.id }).await?;
+        }
+    }
+
+    trans.commit().await?;
+
+    Ok(user)
+}
+
+async fn create_account(
+    db: &DatabaseConnection,
+    identity: Identity,
+) -> Result<entity::users::Model, OAuthError> {
+    // merging on email would let anyone controlling a provider account take over the user
+    if Users::find()
+        .filter(entity::users::Column::Email.eq(identity.email.as_str()))
+        .one(db)
+        .await?
+        .is_some()
+    {
+        return Err(OAuthError::EmailInUse(identity.provider));
+    }
+
+    let username = resolve_username(db, &identity, None).await?;
+
+    let trans = db.begin().await?;
+
+    let user = entity::users::ActiveModel {
+        github_id: Set(github_id(&identity)),
+        username: Set(username),
+        email: Set(identity.email),
+        bio: Set(identity.bio),
+        avatar: Set(identity.avatar_url),
+        permissions: Set(DEFAULT_PERMISSIONS.bits()),
+        last_login_at: Set(Some(Utc::now().naive_utc())),
+        ..Default::default()
+    }
+    .insert(&trans)
+    .await?;
+
+    entity::user_identities::ActiveModel {
+        user_id: Set(user.id),
+        provider: Set(identity.provider.name().to_string()),
+        provider_user_id: Set(identity.provider_user_id),
+        is_primary: Set(true),
+        ..Default::default()
+    }
+    .insert(&trans)
+    .await?;
+
+    trans.commit().await?;
+
+    Ok(user)
+}
+
+/// Links `identity` to an existing account.
+pub async fn link(
+    db: &DatabaseConnection,
+    user: entity::users::Model,
+    identity: Identity,
+) -> Result<(), OAuthError> {
+    if let Some(linked) = find_identity(db, identity.provider, &identity.provider_user_id).await? {
+        return match linked.user_id == user.id {
+            true => Ok(()),
+            false => Err(OAuthError::AlreadyLinked(identity.provider)),
+        };
+    }
+
+    if identities_of(db, user.id)
+        .await?
+        .iter()
+        .any(|i| i.provider == identity.provider.name())
+    {
+        return Err(OAuthError::ProviderLinked(identity.provider));
+    }
+
+    let trans = db.begin().await?;
+
+    entity::user_identities::ActiveModel {
+        user_id: Set(user.id),
+        provider: Set(identity.provider.name().to_string()),
+        provider_user_id: Set(identity.provider_user_id.clone()),
+        is_primary: Set(false),
+        ..Default::default()
+    }
+    .insert(&trans)
+    .await?;
+
+    if let Some(github_id) = github_id(&identity) {
+        let mut am = user.into_active_model();
+        am.github_id = Set(Some(github_id));
+        am.update(&trans).await?;
+    }
+
+    trans.commit().await?;
+
+    Ok(())
+}
+
+/// Removes the identity at `provider` from an account, as long as another one remains.
+pub async fn unlink(
+    db: &DatabaseConnection,
+    user: entity::users::Model,
+    provider: Provider,
+) -> Result<(), OAuthError> {
+    let identities = identities_of(db, user.id).await?;
+
+    let linked = identities
+        .iter()
+        .find(|i| i.provider == provider.name())
+        .ok_or(OAuthError::NotLinked(provider))?;
+
+    if identities.len() == 1 {
+        return Err(OAuthError::LastIdentity);
+    }
+
+    let trans = db.begin().await?;
+
+    UserIdentities::delete_by_id(linked.id).exec(&trans).await?;
+
+    // the oldest remaining identity takes over syncing the profile
+    if linked.is_primary {
+        if let Some(next) = identities.iter().find(|i| i.id != linked.id) {
+            let mut am = next.clone().into_active_model();
+            am.is_primary = Set(true);
+            am.update(&trans).await?;
+        }
+    }
+
+    if provider == Provider::Github {
+        let mut am = user.into_active_model();
+        am.github_id = Set(None);
+        am.update(&trans).await?;
+    }
+
+    trans.commit().await?;
+
+    Ok(())
+}
+
+async fn current_user(
+    req: &HttpRequest,
+    db: &DatabaseConnection,
+) -> Result<entity::users::Model, OAuthError> {
+    match get_user_from_request(req, db).await {
+        Some(user) if validate_permissions(&user, Permission::EDIT_SELF).await => Ok(user),
+        _ => Err(OAuthError::Unauthorized),
+    }
+}
+
+async fn identities_response(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+) -> Result<HttpResponse, OAuthError> {
+    let identities = identities_of(db, user_id)
+        .await?
+        .into_iter()
+        .map(|i| {
+            json!({
+                "provider": i.provider,
+                "primary": i.is_primary,
+                "created_at": i.created_at.and_utc(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(identities))
+}
+
+/// Redirects to the provider's authorization page with a signed `state` that
+/// `/auth/{provider}` checks against the state cookie when the user comes back.
+#[get("/auth/{provider}/authorize")]
+pub async fn authorize(
+    path: web::Path<String>,
+    info: web::Query<AuthorizeReq>,
+) -> Result<HttpResponse, OAuthError> {
+    let (url, nonce) = oauth::authorize_url(provider(&path)?, info.into_inner().redirect_uri)?;
+
+    Ok(HttpResponse::Found()
+        .insert_header(("Location", url))
+        .cookie(oauth::state_cookie(nonce))
+        .finish())
+}
+
+/// Logs in through a provider, or links it to the account when the request is authenticated.
+#[post("/auth/{provider}")]
+pub async fn user_auth(
+    req: HttpRequest,
+    data: web::Data<Database>,
+    path: web::Path<String>,
+    info: web::Query<UserAuthReq>,
+) -> Result<HttpResponse, OAuthError> {
+    let provider = provider(&path)?;
+    let nonce = req.cookie(oauth::STATE_COOKIE).map(|c| c.value().to_string());
+    let state = oauth::verify_state(provider, &info.state, nonce.as_deref())?;
+
+    // check the session before the code is spent on the provider
+    let current = match req.headers().contains_key("Authorization") {
+        true => Some(current_user(&req, &data.pool).await?),
+        false => None,
+    };
+
+    let code = info.code.clone();
+    let identity = web::block(move || {
+        oauth::fetch_identity(provider, &code, state.redirect_uri.as_deref())
+    })
+    .await
+    .map_err(|e| OAuthError::Upstream(provider, e.to_string()))??;
+
+    let mut res = match current {
+        Some(user) => {
+            let user_id = user.id;
+            link(&data.pool, user, identity).await?;
+            identities_response(&data.pool, user_id).await?
+        }
+        None => {
+            let user = login(&data.pool, identity).await?;
+            let jwt = JWTAuth::new(user).encode(*KEY.clone());
+
+            HttpResponse::Ok().json(json!({ "jwt": jwt }))
+        }
+    };
+
+    // the state is spent, and the provider won't accept its code twice anyway
+    let _ = res.add_removal_cookie(&oauth::state_cookie(String::new()));
+    Ok(res)
+}
+
+#[delete("/auth/{provider}")]
+pub async fn unlink_provider(
+    req: HttpRequest,
+    data: web::Data<Database>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, OAuthError> {
+    let provider = provider(&path)?;
+    let user = current_user(&req, &data.pool).await?;
+    let user_id = user.id;
+
+    unlink(&data.pool, user, provider).await?;
+
+    identities_response(&data.pool, user_id).await
+}
+
+#[get("/me/identities")]
+pub async fn list_identities(
+    req: HttpRequest,
+    data: web::Data<Database>,
+) -> Result<HttpResponse, OAuthError> {
+    let user = current_user(&req, &data.pool).await?;
+
+    identities_response(&data.pool, user.id).await
+}
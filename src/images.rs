@@ -0,0 +1,235 @@
+use std::{fmt, io::Cursor};
+
+use image::{imageops::FilterType, io::Reader, DynamicImage, ImageFormat, ImageOutputFormat};
+
+/// Largest image payload accepted by any upload endpoint.
+pub const MAX_IMAGE_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageKind {
+    Icon,
+    Cover,
+    Avatar,
+    Banner,
+    /// An image in a mod's gallery.
+    Screenshot,
+}
+
+struct Limits {
+    min: (u32, u32),
+    max: (u32, u32),
+    square: bool,
+    /// Widths of the generated variants, largest first.
+    widths: &'static [u32],
+    thumbnail: u32,
+}
+
+impl ImageKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Icon => "icon",
+            Self::Cover => "cover",
+            Self::Avatar => "avatar",
+            Self::Banner => "banner",
+            Self::Screenshot => "screenshot",
+        }
+    }
+
+    /// Whether an owner has at most one image of this kind, a new upload replacing the old one.
+    pub fn single(&self) -> bool {
+        !matches!(self, Self::Screenshot)
+    }
+
+    fn limits(&self) -> Limits {
+        match self {
+            Self::Icon | Self::Avatar => Limits {
+                min: (64, 64),
+                max: (2048, 2048),
+                square: true,
+                widths: &[512, 256, 128],
+                thumbnail: 64,
+            },
+            Self::Cover | Self::Banner => Limits {
+                min: (640, 160),
+                max: (4096, 4096),
+                square: false,
+                widths: &[1920, 1280, 640],
+                thumbnail: 320,
+            },
+            Self::Screenshot => Limits {
+                min: (320, 180),
+                max: (4096, 4096),
+                square: false,
+                widths: &[1920, 1280, 640],
+                thumbnail: 320,
+            },
+        }
+    }
+
+    /// Photos compress badly as PNG, so wide images are stored as JPEG.
+    fn output(&self) -> (ImageOutputFormat, &'static str) {
+        match self {
+            Self::Icon | Self::Avatar => (ImageOutputFormat::Png, "png"),
+            Self::Cover | Self::Banner | Self::Screenshot => (ImageOutputFormat::Jpeg(85), "jpg"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    TooLarge(usize),
+    UnsupportedFormat,
+    Decode(String),
+    Dimensions(String),
+    Encode(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge(max) => write!(f, "Image is larger than {} bytes", max),
+            Self::UnsupportedFormat => write!(f, "Image must be a PNG, JPEG or WebP file"),
+            Self::Decode(e) => write!(f, "Image could not be decoded: {}", e),
+            Self::Dimensions(e) => write!(f, "{}", e),
+            Self::Encode(e) => write!(f, "Image could not be encoded: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Failure while turning an upload into a stored image.
+#[derive(Debug)]
+pub enum UploadError {
+    Image(ImageError),
+    Io(std::io::Error),
+    Db(sea_orm::DbErr),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Image(e) => write!(f, "{}", e),
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+/// A single generated file, named relative to the owner's media directory.
+pub struct ImageFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+pub struct ProcessedImage {
+    pub files: Vec<ImageFile>,
+    /// File name of the largest variant.
+    pub primary: String,
+    /// File name of the WebP thumbnail.
+    pub thumbnail: String,
+}
+
+/// Validates an uploaded image and renders the resized variants and WebP thumbnail for `kind`.
+///
+/// `stem` prefixes every generated file name, so a new upload never reuses a cached URL.
+pub fn process(kind: ImageKind, stem: &str, data: &[u8]) -> Result<ProcessedImage, ImageError> {
+    if data.len() > MAX_IMAGE_SIZE {
+        return Err(ImageError::TooLarge(MAX_IMAGE_SIZE));
+    }
+
+    let format = image::guess_format(data).map_err(|_| ImageError::UnsupportedFormat)?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+    ) {
+        return Err(ImageError::UnsupportedFormat);
+    }
+
+    // the header is enough to reject an image by its size, before decoding allocates for it
+    let (width, height) = Reader::with_format(Cursor::new(data), format)
+        .into_dimensions()
+        .map_err(|e| ImageError::Decode(e.to_string()))?;
+
+    let limits = kind.limits();
+
+    if width < limits.min.0 || height < limits.min.1 {
+        return Err(ImageError::Dimensions(format!(
+            "A {} must be at least {}x{} pixels",
+            kind.name(),
+            limits.min.0,
+            limits.min.1
+        )));
+    }
+    if width > limits.max.0 || height > limits.max.1 {
+        return Err(ImageError::Dimensions(format!(
+            "A {} must be at most {}x{} pixels",
+            kind.name(),
+            limits.max.0,
+            limits.max.1
+        )));
+    }
+    if limits.square && width != height {
+        return Err(ImageError::Dimensions(format!(
+            "A {} must be square",
+            kind.name()
+        )));
+    }
+
+    // in case the decoder disagrees with the header
+    let mut decode_limits = image::io::Limits::default();
+    decode_limits.max_image_width = Some(limits.max.0);
+    decode_limits.max_image_height = Some(limits.max.1);
+
+    let mut reader = Reader::with_format(Cursor::new(data), format);
+    reader.limits(decode_limits);
+    let img = reader
+        .decode()
+        .map_err(|e| ImageError::Decode(e.to_string()))?;
+    let width = img.width();
+
+    let (output, ext) = kind.output();
+    let mut files = vec![];
+
+    for &w in limits.widths {
+        // never upscale, the original is used as the largest variant instead
+        let variant = if w < width { resize_to_width(&img, w) } else { img.clone() };
+        files.push(ImageFile {
+            name: format!("{}_{}.{}", stem, w, ext),
+            data: encode(&variant, output.clone())?,
+        });
+    }
+
+    let thumb = resize_to_width(&img, limits.thumbnail.min(width));
+    let thumbnail = format!("{}_thumb.webp", stem);
+    files.push(ImageFile {
+        name: thumbnail.clone(),
+        data: encode(&thumb, ImageOutputFormat::WebP)?,
+    });
+
+    Ok(ProcessedImage {
+        primary: files[0].name.clone(),
+        thumbnail,
+        files,
+    })
+}
+
+fn resize_to_width(img: &DynamicImage, width: u32) -> DynamicImage {
+    let height = ((img.height() as u64 * width as u64) / img.width() as u64).max(1) as u32;
+    img.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+fn encode(img: &DynamicImage, format: ImageOutputFormat) -> Result<Vec<u8>, ImageError> {
+    // jpeg and webp encoders don't take every pixel layout, rgba8/rgb8 are always accepted
+    let img = match format {
+        ImageOutputFormat::Jpeg(_) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        _ => DynamicImage::ImageRgba8(img.to_rgba8()),
+    };
+
+    let mut buf = Cursor::new(Vec::new());
+    img.write_to(&mut buf, format)
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    Ok(buf.into_inner())
+}
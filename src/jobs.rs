@@ -0,0 +1,401 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use entity::prelude::*;
+use juniper::{graphql_value, FieldError, FieldResult, GraphQLEnum, GraphQLObject};
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, QuerySelect, Set, Statement,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{cdn, images::ImageKind, markdown, mods, packages, search};
+
+/// Jobs still running after this long are cancelled and count as failed.
+const TIMEOUT_SECONDS: u64 = 10 * 60;
+/// Jobs left `running` for longer than this belong to a worker that died, and are picked up again.
+const LEASE_SECONDS: i64 = 15 * 60;
+const MAX_BACKOFF_SECONDS: i64 = 60 * 60;
+/// How often finished jobs past their retention are deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Work done after a request has returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
+pub enum Job {
+    /// Adds or replaces the search document of a mod.
+    IndexMod { mod_id: Uuid },
+    /// Uses the icon shipped in a version's package for a mod without one.
+    PackagedIcon { mod_id: Uuid, version_id: Uuid },
+    /// Stores the HTML of a mod's long description, for descriptions written before it was.
+    RenderDescription { mod_id: Uuid },
+}
+
+impl Job {
+    async fn run(self, db: &DatabaseConnection) -> Result<(), String> {
+        match self {
+            Self::IndexMod { mod_id } => search::index_mod(db, mod_id)
+                .await
+                .map_err(|e| e.to_string()),
+            Self::PackagedIcon { mod_id, version_id } => {
+                let db_mod = match Mods::find_by_id(mod_id)
+                    .one(db)
+                    .await
+                    .map_err(|e| e.to_string())?
+                {
+                    // the author uploaded their own icon in the meantime
+                    Some(db_mod) if db_mod.icon.is_none() => db_mod,
+                    _ => return Ok(()),
+                };
+
+                let path = cdn::artifact_path(mod_id, version_id);
+                let icon = web::block(move || {
+                    let file = std::fs::read(path).map_err(|e| e.to_string())?;
+                    match packages::unpack(&*file) {
+                        Some(package) => Ok(mods::packaged_icon(&package.includes)),
+                        None => Err("artifact is not a forgemod".to_string()),
+                    }
+                })
+                .await
+                .map_err(|e| e.to_string())??;
+
+                match icon {
+                    Some(icon) => mods::set_mod_image(db, db_mod, ImageKind::Icon, icon)
+                        .await
+                        .map(|_| ())
+                        // a broken icon won't get better by retrying
+                        .or_else(|e| {
+                            log::warn!("ignoring packaged icon of {}: {}", mod_id, e);
+                            Ok(())
+                        }),
+                    None => Ok(()),
+                }
+            }
+            Self::RenderDescription { mod_id } => {
+                let db_mod = match Mods::find_by_id(mod_id)
+                    .one(db)
+                    .await
+                    .map_err(|e| e.to_string())?
+                {
+                    Some(db_mod) => db_mod,
+                    None => return Ok(()),
+                };
+
+                let html = db_mod.long_description.as_deref().map(markdown::render);
+                let mut am = db_mod.into_active_model();
+                am.long_description_html = Set(html);
+                am.update(db).await.map(|_| ()).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    /// Failed `max_attempts` times and won't be retried on its own.
+    Dead,
+}
+
+impl JobStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Dead => "dead",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "running" => Self::Running,
+            "done" => Self::Done,
+            "dead" => Self::Dead,
+            _ => Self::Pending,
+        }
+    }
+}
+
+#[derive(GraphQLObject, Debug, Clone)]
+pub struct GJob {
+    pub id: uuid::Uuid,
+    pub kind: String,
+    /// The job's arguments as JSON.
+    pub payload: String,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<entity::jobs::Model> for GJob {
+    fn from(j: entity::jobs::Model) -> Self {
+        GJob {
+            id: uuid::Uuid::from_bytes(*j.id.as_bytes()),
+            kind: j.kind,
+            payload: j.payload.to_string(),
+            status: JobStatus::parse(&j.status),
+            attempts: j.attempts,
+            max_attempts: j.max_attempts,
+            run_at: j.run_at.and_utc(),
+            last_error: j.last_error,
+            created_at: j.created_at.and_utc(),
+            updated_at: j.updated_at.and_utc(),
+        }
+    }
+}
+
+/// Queues a job. Enqueueing inside a transaction only makes the job visible once it commits.
+pub async fn enqueue<C: ConnectionTrait>(db: &C, job: Job) -> Result<(), DbErr> {
+    let value = serde_json::to_value(&job).map_err(|e| DbErr::Custom(e.to_string()))?;
+
+    entity::jobs::ActiveModel {
+        kind: Set(value["kind"].as_str().unwrap_or_default().to_string()),
+        payload: Set(value["payload"].clone()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Takes the next due job, marking it as running. `SKIP LOCKED` keeps concurrent workers from
+/// taking the same one.
+///
+/// Jobs whose worker died on their last attempt are marked dead instead of being picked up
+/// again, so a job that takes the process down isn't retried forever.
+async fn claim(db: &DatabaseConnection) -> Result<Option<entity::jobs::Model>, DbErr> {
+    db.execute(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        r#"UPDATE jobs
+           SET status = 'dead', last_error = 'worker stopped while running the job',
+               updated_at = now()
+           WHERE status = 'running'
+             AND updated_at < now() - $1 * interval '1 second'
+             AND attempts >= max_attempts"#,
+        [LEASE_SECONDS.into()],
+    ))
+    .await?;
+
+    Jobs::find()
+        .from_raw_sql(Statement::from_sql_and_values(
+            db.get_database_backend(),
+            r#"UPDATE jobs
+               SET status = 'running', attempts = attempts + 1, updated_at = now()
+               WHERE id = (
+                   SELECT id FROM jobs
+                   WHERE (status = 'pending' AND run_at <= now())
+                      OR (status = 'running'
+                          AND updated_at < now() - $1 * interval '1 second'
+                          AND attempts < max_attempts)
+                   ORDER BY run_at
+                   FOR UPDATE SKIP LOCKED
+                   LIMIT 1
+               )
+               RETURNING *"#,
+            [LEASE_SECONDS.into()],
+        ))
+        .one(db)
+        .await
+}
+
+async fn finish(
+    db: &DatabaseConnection,
+    job: entity::jobs::Model,
+    result: Result<(), String>,
+) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+    let attempts = job.attempts;
+    let max_attempts = job.max_attempts;
+    let mut am = job.into_active_model();
+
+    match result {
+        Ok(()) => {
+            am.status = Set(JobStatus::Done.as_str().to_string());
+            am.last_error = Set(None);
+        }
+        Err(e) if attempts >= max_attempts => {
+            am.status = Set(JobStatus::Dead.as_str().to_string());
+            am.last_error = Set(Some(e));
+        }
+        Err(e) => {
+            // 30s, 1m, 2m, ... capped at an hour
+            let backoff = (30i64 << (attempts - 1).clamp(0, 16)).min(MAX_BACKOFF_SECONDS);
+            am.status = Set(JobStatus::Pending.as_str().to_string());
+            am.run_at = Set(now + chrono::Duration::seconds(backoff));
+            am.last_error = Set(Some(e));
+        }
+    }
+    am.updated_at = Set(now);
+    am.update(db).await?;
+
+    Ok(())
+}
+
+/// Seconds an idle worker waits before looking for jobs again, from `JOB_POLL_INTERVAL`.
+fn poll_interval() -> Duration {
+    Duration::from_secs(
+        std::env::var("JOB_POLL_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(5),
+    )
+}
+
+/// Number of workers to run, from `JOB_WORKERS`.
+pub fn worker_count() -> usize {
+    std::env::var("JOB_WORKERS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(2)
+}
+
+/// Days finished jobs are kept for, from `JOB_RETENTION_DAYS`. `0` keeps them forever. Dead
+/// jobs are always kept, until they are retried.
+fn retention_days() -> Option<i64> {
+    let days = std::env::var("JOB_RETENTION_DAYS")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(7);
+
+    (days > 0).then_some(days)
+}
+
+/// Deletes jobs that finished more than `days` days ago.
+pub async fn prune(db: &DatabaseConnection, days: i64) -> Result<u64, DbErr> {
+    Ok(Jobs::delete_many()
+        .filter(entity::jobs::Column::Status.eq(JobStatus::Done.as_str()))
+        .filter(
+            entity::jobs::Column::UpdatedAt
+                .lt(Utc::now().naive_utc() - chrono::Duration::days(days)),
+        )
+        .exec(db)
+        .await?
+        .rows_affected)
+}
+
+/// Deletes finished jobs past their retention every [`PRUNE_INTERVAL`], for as long as the
+/// server runs.
+pub async fn run_pruner(db: DatabaseConnection) {
+    let days = match retention_days() {
+        Some(days) => days,
+        None => return,
+    };
+
+    loop {
+        match prune(&db, days).await {
+            Ok(deleted) => log::info!("jobs: deleted {} finished jobs", deleted),
+            Err(e) => log::error!("failed to delete finished jobs: {}", e),
+        }
+
+        actix_web::rt::time::sleep(PRUNE_INTERVAL).await;
+    }
+}
+
+/// Runs a job on a task of its own, so a job that panics fails its attempt instead of taking the
+/// worker down with it.
+async fn execute(db: &DatabaseConnection, task: Job) -> Result<(), String> {
+    let db = db.clone();
+    let mut handle = actix_web::rt::spawn(async move { task.run(&db).await });
+
+    match actix_web::rt::time::timeout(Duration::from_secs(TIMEOUT_SECONDS), &mut handle).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => {
+            handle.abort();
+            Err(format!("timed out after {} seconds", TIMEOUT_SECONDS))
+        }
+    }
+}
+
+/// Runs jobs for as long as the server runs.
+pub async fn run_worker(db: DatabaseConnection) {
+    let idle = poll_interval();
+
+    loop {
+        let job = match claim(&db).await {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                actix_web::rt::time::sleep(idle).await;
+                continue;
+            }
+            Err(e) => {
+                log::error!("failed to claim job: {}", e);
+                actix_web::rt::time::sleep(idle).await;
+                continue;
+            }
+        };
+
+        let result = match serde_json::from_value::<Job>(json!({
+            "kind": job.kind,
+            "payload": job.payload,
+        })) {
+            Ok(task) => execute(&db, task).await,
+            Err(e) => Err(format!("unknown job: {}", e)),
+        };
+
+        if let Err(e) = &result {
+            log::warn!("job {} ({}) failed: {}", job.id, job.kind, e);
+        }
+
+        let id = job.id;
+        if let Err(e) = finish(&db, job, result).await {
+            log::error!("failed to record result of job {}: {}", id, e);
+        }
+    }
+}
+
+pub async fn find_all(
+    db: &DatabaseConnection,
+    status: Option<JobStatus>,
+    limit: i32,
+    offset: i32,
+) -> FieldResult<Vec<GJob>> {
+    let mut query = Jobs::find();
+    if let Some(status) = status {
+        query = query.filter(entity::jobs::Column::Status.eq(status.as_str()));
+    }
+
+    Ok(query
+        .order_by_desc(entity::jobs::Column::CreatedAt)
+        .limit(limit.max(0) as u64)
+        .offset(offset.max(0) as u64)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(GJob::from)
+        .collect())
+}
+
+/// Puts a job back in the queue to run right away, with a fresh set of attempts.
+pub async fn retry(db: &DatabaseConnection, id: uuid::Uuid) -> FieldResult<GJob> {
+    let job = Jobs::find_by_id(Uuid::from_bytes(*id.as_bytes()))
+        .one(db)
+        .await?
+        .ok_or_else(|| FieldError::new("Job not found", graphql_value!({ "notFound": "Job not found" })))?;
+
+    if job.status == JobStatus::Running.as_str() {
+        return Err(FieldError::new(
+            "Job is running",
+            graphql_value!({ "status": "Job is running" }),
+        ));
+    }
+
+    let now = Utc::now().naive_utc();
+    let mut am = job.into_active_model();
+    am.status = Set(JobStatus::Pending.as_str().to_string());
+    am.attempts = Set(0);
+    am.run_at = Set(now);
+    am.updated_at = Set(now);
+
+    Ok(am.update(db).await?.into())
+}
@@ -7,7 +7,6 @@ use actix_web::{
     App, HttpResponse, HttpServer, Error, get, Responder,
 };
 use cached::async_sync::OnceCell;
-use meilisearch_sdk::settings::Settings;
 use migration::MigratorTrait;
 use rand::Rng;
 use sea_orm::{EntityTrait, PaginatorTrait, DatabaseConnection};
@@ -16,8 +15,26 @@ mod schema;
 mod users;
 mod mods;
 mod versions;
+mod analysis;
 mod auth;
+mod categories;
 mod cdn;
+mod compatibility;
+mod downloads;
+mod gallery;
+mod game_versions;
+mod identities;
+mod images;
+mod jobs;
+mod markdown;
+mod oauth;
+mod packages;
+mod ratings;
+mod reconcile;
+mod search;
+mod trending;
+mod updates;
+mod validation;
 
 use crate::schema::{create_schema, Schema};
 
@@ -114,10 +131,21 @@ async fn main() -> io::Result<()> {
     migration::Migrator::up(&db_conn, None).await.unwrap();
 
     // set meilisearch settings
-    let client = meilisearch_sdk::client::Client::new(std::env::var("MEILI_URL").unwrap(), Some(std::env::var("MEILI_KEY").unwrap()));
+    search::apply_settings().await.unwrap();
 
-    let settings = Settings::new().with_filterable_attributes(&["category"]).with_searchable_attributes(&["name", "description"]).with_sortable_attributes(&["stats.downloads", "created_at", "updated_at"]);
-    client.index(format!("{}_mods", std::env::var("MEILI_PREFIX").unwrap_or("".to_string()))).set_settings(&settings).await.unwrap();
+    // periodically check that stored files, search and the database agree
+    actix_web::rt::spawn(reconcile::run(db_conn.clone()));
+
+    // periodically recompute trending scores from recent downloads
+    actix_web::rt::spawn(trending::run(db_conn.clone()));
+
+    // run work queued by requests, like indexing uploads
+    for _ in 0..jobs::worker_count() {
+        actix_web::rt::spawn(jobs::run_worker(db_conn.clone()));
+    }
+
+    // keep the queue from growing without bound
+    actix_web::rt::spawn(jobs::run_pruner(db_conn.clone()));
 
     // Start HTTP server
     HttpServer::new( move || {
@@ -135,11 +163,21 @@ async fn main() -> io::Result<()> {
             )
             .service(web::resource("/playground").route(web::get().to(playground_route)))
             .service(web::resource("/graphiql").route(web::get().to(graphiql_route)))
-            .service(users::user_auth)
+            .service(identities::authorize)
+            .service(identities::user_auth)
+            .service(identities::unlink_provider)
+            .service(identities::list_identities)
             .service(mods::create_mod)
+            .service(mods::upload_icon)
+            .service(mods::upload_cover)
+            .service(gallery::upload_screenshot)
+            .service(updates::check_updates)
             .service(cdn::cdn_get)
+            .service(cdn::cdn_media)
             .service(index)
             .service(users::get_me)
+            .service(users::upload_avatar)
+            .service(users::upload_banner)
             // the graphiql UI requires CORS to be enabled
             .wrap(Cors::permissive())
             .wrap(middleware::Logger::default())
@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use ammonia::{Builder, UrlRelative};
+use pulldown_cmark::{html, Options, Parser};
+
+/// Renders user written Markdown to HTML that is safe to embed in a page.
+///
+/// Raw HTML in the Markdown is passed through the sanitizer like everything else, so scripts,
+/// event handlers and `javascript:` links never survive. Relative links are dropped since there
+/// is no page they could be relative to that the website and mod managers share. Inputs are only
+/// kept as the disabled checkboxes of task lists, whatever the Markdown asked for.
+pub fn render(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+
+    Builder::default()
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .url_relative(UrlRelative::Deny)
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .add_tags(&["input"])
+        .add_tag_attributes("input", &["checked"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .set_tag_attribute_value("input", "disabled", "")
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_scripts_and_event_handlers() {
+        let html = render(
+            "<script>alert(1)</script>\n\n\
+             <img src=\"https://example.com/a.png\" onerror=\"alert(1)\">",
+        );
+
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("alert(1)"));
+        assert!(!html.contains("onerror"));
+        assert!(html.contains("<img src=\"https://example.com/a.png\">"));
+    }
+
+    #[test]
+    fn drops_javascript_links() {
+        let html = render("[click](javascript:alert(1)) <a href=\"javascript:alert(1)\">raw</a>");
+
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("click"));
+    }
+
+    #[test]
+    fn drops_relative_links() {
+        let html = render("[docs](/docs) [up](../readme.md)");
+
+        assert!(!html.contains("href"));
+        assert!(html.contains("docs"));
+    }
+
+    #[test]
+    fn marks_links_nofollow() {
+        let html = render("[site](https://example.com)");
+
+        assert!(html.contains(
+            "<a href=\"https://example.com\" rel=\"noopener noreferrer nofollow\">site</a>"
+        ));
+    }
+
+    #[test]
+    fn disables_task_list_checkboxes() {
+        let html = render("- [x] done\n- [ ] todo");
+
+        assert_eq!(html.matches("<input").count(), 2);
+        assert_eq!(html.matches("type=\"checkbox\"").count(), 2);
+        assert_eq!(html.matches("disabled=\"\"").count(), 2);
+        assert_eq!(html.matches("checked").count(), 1);
+    }
+
+    #[test]
+    fn only_keeps_checkboxes() {
+        let html = render("<input type=\"text\" value=\"x\" onfocus=\"alert(1)\" autofocus>");
+
+        assert!(html.contains("type=\"checkbox\""));
+        assert!(!html.contains("type=\"text\""));
+        assert!(!html.contains("onfocus"));
+        assert!(!html.contains("autofocus"));
+    }
+}
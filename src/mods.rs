@@ -1,45 +1,167 @@
 use std::vec;
 
 use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 
-use forge_lib::structs::{forgemod::ForgeMod, v1::{unpack_v1_forgemod, ForgeModTypes}};
-use futures::StreamExt;
-use juniper::{graphql_value, FieldError, FieldResult, GraphQLObject};
+use forge_lib::structs::{
+    forgemod::ForgeMod,
+    v1::IncludeData,
+};
+use juniper::{
+    graphql_value, FieldError, FieldResult, GraphQLEnum, GraphQLInputObject, GraphQLObject,
+};
 use migration::OnConflict;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set,
-    TransactionTrait,
+    sea_query::Query, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, IntoActiveModel, JoinType, Order, QueryFilter, QueryOrder, QuerySelect,
+    RelationTrait, Set, SqlErr, TransactionTrait,
 };
 use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 
 use entity::prelude::*;
-use meilisearch_entity::prelude::*;
 
 use crate::{
-    auth::{validate_permissions, Authorization, Permission},
+    analysis::{self, AnalysisReport, AssemblyCheck},
+    auth::{can_edit_mod, get_user_from_request, validate_permissions, Permission},
+    categories, cdn,
+    downloads::{self, GDownloadHistory, Granularity},
+    gallery::{self, GMedia},
+    game_versions,
+    images::{self, ImageKind, UploadError},
+    jobs, markdown,
+    packages::{self, ModType},
+    search,
+    validation::{self, ModManifest, MAX_LONG_DESCRIPTION_LEN},
     versions::{self, GVersion},
     Database,
 };
 
-#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+const MAX_TAGS: usize = 10;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Mod {
     pub id: Uuid,
     pub slug: String,
+    pub mod_type: ModType,
     pub name: String,
     pub description: Option<String>,
+    pub long_description: Option<String>,
+    pub description_html: Option<String>,
     pub icon: Option<String>,
     pub cover: Option<String>,
+    pub gallery: Vec<GMedia>,
     pub author: ModAuthor,
     pub category: ModCategory,
+    pub categories: Vec<ModCategory>,
+    pub tags: Vec<String>,
     pub stats: GModStats,
     pub versions: Vec<GVersion>,
     pub updated_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
 }
 
+/// Resolved by hand rather than derived, since `downloadHistory` takes arguments and reads the
+/// database.
+#[juniper::graphql_object(context = Database)]
+impl Mod {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn slug(&self) -> &str {
+        &self.slug
+    }
+
+    fn mod_type(&self) -> ModType {
+        self.mod_type
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Long-form Markdown description, from the package's README or edited by the author.
+    fn long_description(&self) -> Option<&str> {
+        self.long_description.as_deref()
+    }
+
+    /// `long_description` rendered to sanitized HTML.
+    fn description_html(&self) -> Option<&str> {
+        self.description_html.as_deref()
+    }
+
+    fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+
+    fn cover(&self) -> Option<&str> {
+        self.cover.as_deref()
+    }
+
+    /// Screenshots and videos, in the order the author chose.
+    fn gallery(&self) -> &[GMedia] {
+        &self.gallery
+    }
+
+    fn author(&self) -> &ModAuthor {
+        &self.author
+    }
+
+    fn category(&self) -> &ModCategory {
+        &self.category
+    }
+
+    fn categories(&self) -> &[ModCategory] {
+        &self.categories
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn stats(&self) -> &GModStats {
+        &self.stats
+    }
+
+    fn versions(&self) -> &[GVersion] {
+        &self.versions
+    }
+
+    /// Downloads per period between `from` and `to`, both included. Defaults to the last 30 days,
+    /// by day.
+    async fn download_history(
+        &self,
+        db: &Database,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        granularity: Option<Granularity>,
+    ) -> FieldResult<GDownloadHistory> {
+        downloads::history(
+            &db.pool,
+            sea_orm::prelude::Uuid::from_bytes(*self.id.as_bytes()),
+            from,
+            to,
+            granularity.unwrap_or(Granularity::Day),
+        )
+        .await
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
 #[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
 pub struct ModAuthor {
     pub id: Uuid,
@@ -63,13 +185,37 @@ impl Mod {
         let category = Categories::find_by_id(m.category).one(db).await?.unwrap();
         let stats = ModStats::find_by_id(m.stats).one(db).await?.unwrap();
         let author = Users::find_by_id(m.author).one(db).await?.unwrap();
+        let categories = ModCategories::find()
+            .filter(entity::mod_categories::Column::ModId.eq(m.id))
+            .find_also_related(Categories)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|(_, c)| c)
+            .map(|c| ModCategory {
+                name: c.name,
+                desc: c.description,
+            })
+            .collect::<Vec<_>>();
+        let tags = ModTags::find()
+            .filter(entity::mod_tags::Column::ModId.eq(m.id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|t| t.tag)
+            .collect::<Vec<_>>();
+        let gallery = gallery::find_by_mod(db, m.id).await?;
         Ok(Mod {
             id: Uuid::from_bytes(*m.id.as_bytes()),
             slug: m.slug,
+            mod_type: ModType::from_name(&m.mod_type).unwrap_or(ModType::Mod),
             name: m.name,
             description: m.description,
+            description_html: m.long_description_html,
+            long_description: m.long_description,
             icon: m.icon,
             cover: m.cover,
+            gallery,
             author: ModAuthor {
                 id: Uuid::from_bytes(*author.id.as_bytes()),
                 username: author.username,
@@ -85,8 +231,13 @@ impl Mod {
                 name: category.name,
                 desc: category.description,
             },
+            categories,
+            tags,
             stats: GModStats {
                 downloads: stats.downloads,
+                rating: stats.rating,
+                rating_count: stats.rating_count,
+                trending_score: stats.trending_score,
             },
             versions: versions::find_by_mod_id(db, Uuid::from_bytes(*m.id.as_bytes())).await?,
             updated_at: m.updated_at.and_utc(),
@@ -98,8 +249,12 @@ impl Mod {
 #[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
 pub struct GModStats {
     pub downloads: i32,
-    // pub rating: f32,
-    // pub rating_count: i32,
+    /// Average rating from 1 to 5, 0 while the mod has none.
+    pub rating: f64,
+    pub rating_count: i32,
+    /// Recent downloads weighted by how much older ones have decayed and by the rating.
+    /// Refreshed periodically, so it trails new downloads a bit.
+    pub trending_score: f64,
 }
 
 #[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
@@ -108,46 +263,212 @@ pub struct ModCategory {
     pub desc: String,
 }
 
+#[derive(GraphQLInputObject, Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ModFilter {
+    /// Category name, e.g. `gameplay`.
+    pub category: Option<String>,
+    /// Username of the mod author.
+    pub author: Option<String>,
+    /// Semver requirement matched against supported game versions, e.g. `>=1.29.0, <1.31.0`.
+    pub game_version: Option<String>,
+    /// Only return mods with at least one approved version.
+    pub approved_only: Option<bool>,
+    pub mod_type: Option<ModType>,
+    pub updated_since: Option<DateTime<Utc>>,
+}
+
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModSort {
+    Downloads,
+    Rating,
+    /// Trending first, then the most downloaded mods for those that aren't trending.
+    Trending,
+    Name,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl From<SortOrder> for Order {
+    fn from(order: SortOrder) -> Self {
+        match order {
+            SortOrder::Asc => Order::Asc,
+            SortOrder::Desc => Order::Desc,
+        }
+    }
+}
+
 pub async fn find_all(
     db: &DatabaseConnection,
     limit: i32,
     offset: i32,
     version: Option<String>,
+    filter: ModFilter,
+    sort: Option<ModSort>,
+    order: Option<SortOrder>,
 ) -> FieldResult<Vec<Mod>> {
     let limit = limit as u64;
     let offset = offset as u64;
 
+    let mut query = Mods::find();
+
     if let Some(version) = version {
-        let verid = BeatSaberVersions::find()
-            .filter(entity::beat_saber_versions::Column::Ver.eq(version))
-            .one(db)
+        let ver = game_versions::resolve(db, &version)
             .await?
-            .unwrap()
-            .id;
+            .ok_or_else(|| {
+                FieldError::new(
+                    "Unknown game version",
+                    graphql_value!({ "version": "Unknown game version" }),
+                )
+            })?;
+
+        query = query.filter(
+            entity::mods::Column::Id.in_subquery(
+                Query::select()
+                    .column(entity::mod_beat_saber_versions::Column::ModId)
+                    .from(ModBeatSaberVersions)
+                    .and_where(
+                        entity::mod_beat_saber_versions::Column::BeatSaberVersionId.eq(ver.id),
+                    )
+                    .to_owned(),
+            ),
+        );
+    }
+
+    if let Some(game_version) = filter.game_version {
+        let req = VersionReq::parse(&game_version).map_err(|e| {
+            FieldError::new(
+                "Invalid game version range",
+                graphql_value!({ "gameVersion": (e.to_string()) }),
+            )
+        })?;
 
-        let mods = ModBeatSaberVersions::find()
-            .filter(entity::mod_beat_saber_versions::Column::BeatSaberVersionId.eq(verid))
-            .find_also_related(Mods)
+        let ver_ids = BeatSaberVersions::find()
             .all(db)
             .await?
-            .iter()
-            .map(|v| v.1.clone().unwrap())
+            .into_iter()
+            .filter(|v| Version::parse(&v.ver).map_or(false, |v| req.matches(&v)))
+            .map(|v| v.id)
             .collect::<Vec<_>>();
 
-        let mut r = vec![];
-        for m in mods {
-            r.push(Mod::from_db_mod(db, m).await.unwrap());
+        if ver_ids.is_empty() {
+            return Ok(vec![]);
         }
-        Ok(r)
-    } else {
-        let mods = Mods::find().limit(limit).offset(offset).all(db).await?;
 
-        let mut r = vec![];
-        for m in mods {
-            r.push(Mod::from_db_mod(db, m).await.unwrap());
-        }
-        Ok(r)
+        query = query.filter(
+            entity::mods::Column::Id.in_subquery(
+                Query::select()
+                    .column(entity::mod_beat_saber_versions::Column::ModId)
+                    .from(ModBeatSaberVersions)
+                    .and_where(
+                        entity::mod_beat_saber_versions::Column::BeatSaberVersionId.is_in(ver_ids),
+                    )
+                    .to_owned(),
+            ),
+        );
     }
+
+    if let Some(category) = filter.category {
+        let cata = Categories::find()
+            .filter(entity::categories::Column::Name.eq(category))
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                FieldError::new(
+                    "Unknown category",
+                    graphql_value!({ "category": "Unknown category" }),
+                )
+            })?;
+
+        query = query.filter(
+            entity::mods::Column::Id.in_subquery(
+                Query::select()
+                    .column(entity::mod_categories::Column::ModId)
+                    .from(ModCategories)
+                    .and_where(entity::mod_categories::Column::CategoryId.eq(cata.id))
+                    .to_owned(),
+            ),
+        );
+    }
+
+    if let Some(author) = filter.author {
+        let author = Users::find()
+            .filter(entity::users::Column::Username.eq(author))
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                FieldError::new(
+                    "Unknown author",
+                    graphql_value!({ "author": "Unknown author" }),
+                )
+            })?;
+
+        query = query.filter(entity::mods::Column::Author.eq(author.id));
+    }
+
+    if filter.approved_only.unwrap_or(false) {
+        query = query.filter(
+            entity::mods::Column::Id.in_subquery(
+                Query::select()
+                    .column(entity::versions::Column::ModId)
+                    .from(Versions)
+                    .and_where(entity::versions::Column::Approved.eq(true))
+                    .to_owned(),
+            ),
+        );
+    }
+
+    if let Some(mod_type) = filter.mod_type {
+        query = query.filter(entity::mods::Column::ModType.eq(mod_type.name()));
+    }
+
+    if let Some(since) = filter.updated_since {
+        query = query.filter(entity::mods::Column::UpdatedAt.gte(since.naive_utc()));
+    }
+
+    let sort = sort.unwrap_or(ModSort::CreatedAt);
+    let order: Order = order
+        .unwrap_or(match sort {
+            ModSort::Name => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        })
+        .into();
+
+    query = match sort {
+        ModSort::Downloads => query
+            .join(JoinType::InnerJoin, entity::mods::Relation::ModStats.def())
+            .order_by(entity::mod_stats::Column::Downloads, order),
+        ModSort::Rating => query
+            .join(JoinType::InnerJoin, entity::mods::Relation::ModStats.def())
+            .order_by(entity::mod_stats::Column::Rating, order)
+            .order_by(entity::mod_stats::Column::RatingCount, order),
+        ModSort::Trending => query
+            .join(JoinType::InnerJoin, entity::mods::Relation::ModStats.def())
+            .order_by(entity::mod_stats::Column::TrendingScore, order)
+            .order_by(entity::mod_stats::Column::Downloads, order),
+        ModSort::Name => query.order_by(entity::mods::Column::Name, order),
+        ModSort::CreatedAt => query.order_by(entity::mods::Column::CreatedAt, order),
+        ModSort::UpdatedAt => query.order_by(entity::mods::Column::UpdatedAt, order),
+    };
+
+    // keep pagination stable when the sort key ties
+    let mods = query
+        .order_by_asc(entity::mods::Column::Id)
+        .limit(limit)
+        .offset(offset)
+        .all(db)
+        .await?;
+
+    let mut r = vec![];
+    for m in mods {
+        r.push(Mod::from_db_mod(db, m).await?);
+    }
+    Ok(r)
 }
 
 pub async fn find_by_id(db: &DatabaseConnection, id: Uuid) -> FieldResult<Mod> {
@@ -195,403 +516,860 @@ pub async fn find_by_author(db: &DatabaseConnection, author: Uuid) -> FieldResul
     Ok(r)
 }
 
+fn invalid_tag() -> FieldError {
+    FieldError::new(
+        "Tags must be 1-32 lowercase letters, digits or dashes",
+        graphql_value!({ "tags": "Tags must be 1-32 lowercase letters, digits or dashes" }),
+    )
+}
+
+pub async fn set_categories(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    categories: Vec<String>,
+    tags: Vec<String>,
+) -> FieldResult<Mod> {
+    let m = Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Mod not found",
+                graphql_value!({ "internal_error": "Mod not found" }),
+            )
+        })?;
+
+    if !can_edit_mod(user, &m).await {
+        return Err(FieldError::new(
+            "Unauthorized",
+            graphql_value!({ "auth": "Unauthorized" }),
+        ));
+    }
+
+    if categories.is_empty() {
+        return Err(FieldError::new(
+            "A mod needs at least one category",
+            graphql_value!({ "categories": "A mod needs at least one category" }),
+        ));
+    }
+
+    let mut db_catas = vec![];
+    for name in categories {
+        let cata = categories::find_by_name(db, &name).await?;
+        if cata.retired {
+            return Err(FieldError::new(
+                format!("Category `{}` has been retired", name),
+                graphql_value!({ "categories": "Category has been retired" }),
+            ));
+        }
+        if !db_catas.iter().any(|c: &entity::categories::Model| c.id == cata.id) {
+            db_catas.push(cata);
+        }
+    }
+
+    let mut tags = tags
+        .into_iter()
+        .map(|t| t.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+
+    if tags.len() > MAX_TAGS {
+        return Err(FieldError::new(
+            format!("A mod can have at most {} tags", MAX_TAGS),
+            graphql_value!({ "tags": "Too many tags" }),
+        ));
+    }
+    if tags.iter().any(|t| {
+        t.is_empty()
+            || t.len() > 32
+            || !t
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    }) {
+        return Err(invalid_tag());
+    }
+
+    let trans = db.begin().await?;
+
+    ModCategories::delete_many()
+        .filter(entity::mod_categories::Column::ModId.eq(m.id))
+        .exec(&trans)
+        .await?;
+    for cata in &db_catas {
+        entity::mod_categories::ActiveModel {
+            mod_id: Set(m.id),
+            category_id: Set(cata.id),
+        }
+        .insert(&trans)
+        .await?;
+    }
+
+    ModTags::delete_many()
+        .filter(entity::mod_tags::Column::ModId.eq(m.id))
+        .exec(&trans)
+        .await?;
+    for tag in tags {
+        entity::mod_tags::ActiveModel {
+            mod_id: Set(m.id),
+            tag: Set(tag),
+        }
+        .insert(&trans)
+        .await?;
+    }
+
+    // the primary category has to stay one of the mod's categories
+    let m = if db_catas.iter().any(|c| c.id == m.category) {
+        m
+    } else {
+        let mut am = m.into_active_model();
+        am.category = Set(db_catas[0].id);
+        am.update(&trans).await?
+    };
+
+    trans.commit().await?;
+
+    if let Err(e) = search::index_mod(db, m.id).await {
+        log::error!("failed to reindex mod {}: {}", m.id, e);
+    }
+
+    Mod::from_db_mod(db, m).await
+}
+
+/// Replaces the long description of a mod. The next upload that ships a README replaces it
+/// again.
+pub async fn set_long_description(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    description: Option<String>,
+) -> FieldResult<Mod> {
+    let m = Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Mod not found",
+                graphql_value!({ "internal_error": "Mod not found" }),
+            )
+        })?;
+
+    if !can_edit_mod(user, &m).await {
+        return Err(FieldError::new(
+            "Unauthorized",
+            graphql_value!({ "auth": "Unauthorized" }),
+        ));
+    }
+
+    let description = description
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty());
+    if description
+        .as_ref()
+        .is_some_and(|d| d.chars().count() > MAX_LONG_DESCRIPTION_LEN)
+    {
+        return Err(FieldError::new(
+            format!(
+                "The description must be at most {} characters",
+                MAX_LONG_DESCRIPTION_LEN
+            ),
+            graphql_value!({ "description": "Description is too long" }),
+        ));
+    }
+
+    let mut am = m.into_active_model();
+    am.long_description_html = Set(description.as_deref().map(markdown::render));
+    am.long_description = Set(description);
+    am.updated_at = Set(Utc::now().naive_utc());
+
+    Mod::from_db_mod(db, am.update(db).await?).await
+}
+
 #[post("/mods")]
 pub async fn create_mod(
     db: web::Data<Database>,
-    mut payload: web::Payload,
+    payload: web::Payload,
     req: HttpRequest,
 ) -> impl Responder {
-    let auth = req
-        .headers()
-        .get("Authorization")
-        .unwrap()
-        .to_str()
-        .unwrap();
-    let auser;
-    if auth.starts_with("Bearer") {
-        let auth = Authorization::parse(Some(auth.split(" ").collect::<Vec<_>>()[1].to_string()));
-        let user = auth.get_user(&db.pool).await.unwrap();
-        if !validate_permissions(&user, Permission::CREATE_MOD).await {
-            return HttpResponse::Unauthorized().body("Unauthorized");
-        }
-        auser = user;
-    } else {
-        return HttpResponse::Unauthorized().body("Unauthorized");
-    }
-
-    let mut buf = Vec::new();
+    // nothing of the body is read until the uploader is known to be allowed to upload
+    let auser = match get_user_from_request(&req, &db.pool).await {
+        Some(user) if validate_permissions(&user, Permission::CREATE_MOD).await => user,
+        _ => return HttpResponse::Unauthorized().body("Unauthorized"),
+    };
 
-    while let Some(item) = payload.next().await {
-        let item = item.unwrap();
-        buf.extend_from_slice(&item);
+    let limit = cdn::upload_limit(&auser);
+    if let Err(res) = cdn::check_content_length(&req, limit) {
+        return res;
     }
 
-    let forgemod = {
-        let fm = unpack_v1_forgemod(&*buf).unwrap();
-        match fm {
-            ForgeModTypes::Mod(fm) => fm,
-            _ => return HttpResponse::BadRequest().body("Invalid ForgeMod"),
+    let mut upload = match cdn::spool_limited(payload, limit).await {
+        Ok(upload) => upload,
+        Err(res) => return res,
+    };
+
+    let package = match packages::unpack(&mut upload) {
+        Some(package) => package,
+        None => {
+            return validation::problems_response(vec![
+                "The file is not a valid forgemod archive".to_string(),
+            ])
         }
     };
 
-    let manifest = forgemod.manifest.inner.clone();
+    let release_notes = packaged_markdown(&package.includes, "RELEASE_NOTES.md");
+    let readme = packaged_markdown(&package.includes, "README.md");
+    let problems = validation::validate_manifest(&ModManifest {
+        slug: &package.id,
+        name: &package.name,
+        description: &package.description,
+        website: &package.website,
+        artifact_size: package.artifact.as_ref().map(Vec::len),
+        release_notes: release_notes.as_deref(),
+        long_description: readme.as_deref(),
+    });
+    if !problems.is_empty() {
+        return validation::problems_response(problems);
+    }
 
-    let db_cata = Categories::find()
-        .filter(entity::categories::Column::Name.eq(manifest.category.clone().to_string()))
+    let existing = match Mods::find()
+        .filter(entity::mods::Column::Slug.eq(package.id.as_str()))
         .one(&db.pool)
         .await
-        .unwrap();
+    {
+        Ok(existing) => existing,
+        Err(e) => {
+            log::error!("failed to look up mod {}: {}", package.id, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
 
-    // if cata does not exist, default to other
-    let db_cata = if let Some(db_cata) = db_cata {
-        db_cata
-    } else {
-        Categories::find()
-            .filter(entity::categories::Column::Name.eq("other"))
+    if let Some(existing) = existing {
+        // new versions of a mod come from whoever may edit it
+        if !can_edit_mod(&auser, &existing).await {
+            return HttpResponse::Forbidden()
+                .body(format!("`{}` belongs to another user", package.id));
+        }
+
+        // a slug keeps the type it was first uploaded with
+        if existing.mod_type != package.mod_type.name() {
+            return validation::problems_response(vec![format!(
+                "`{}` is a {}, it can't be uploaded as a {}",
+                package.id,
+                existing.mod_type,
+                package.mod_type.name()
+            )]);
+        }
+
+        match Versions::find()
+            .filter(entity::versions::Column::ModId.eq(existing.id))
+            .filter(entity::versions::Column::Version.eq(package.version.to_string()))
             .one(&db.pool)
             .await
-            .unwrap()
-            .unwrap()
+        {
+            Ok(None) => {}
+            Ok(Some(_)) => {
+                return validation::problems_response(vec![format!(
+                    "Version {} of `{}` was already uploaded",
+                    package.version, package.id
+                )])
+            }
+            Err(e) => {
+                log::error!("failed to look up {}@{}: {}", package.id, package.version, e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    let mut analysis = package
+        .artifact
+        .as_deref()
+        .map(analysis::analyse)
+        .unwrap_or_default();
+    if analysis.is_risky() {
+        log::warn!(
+            "upload of {}@{} by {} was flagged: {:?}",
+            package.id,
+            package.version,
+            auser.username,
+            analysis.findings
+        );
+    }
+
+    let check = analysis::assembly_check();
+    if check != AssemblyCheck::Off {
+        match declared_assemblies(&db.pool, &package.depends, &package.includes).await {
+            Ok(Some(declared)) => {
+                analysis.undeclared_references =
+                    analysis::undeclared_references(&analysis, &declared);
+            }
+            // any reference could be satisfied by the dependency we know nothing about
+            Ok(None) => log::info!(
+                "skipping the assembly check of {}@{}, a dependency predates dll scanning",
+                package.id,
+                package.version
+            ),
+            Err(e) => {
+                log::error!("failed to look up dependency assemblies: {}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+
+        if check == AssemblyCheck::Reject && !analysis.undeclared_references.is_empty() {
+            return validation::problems_response(
+                analysis
+                    .undeclared_references
+                    .iter()
+                    .map(|r| {
+                        format!(
+                            "The dll references `{}`, which no dependency ships. Add the mod providing it to `depends`",
+                            r
+                        )
+                    })
+                    .collect(),
+            );
+        }
+        if !analysis.undeclared_references.is_empty() {
+            log::warn!(
+                "{}@{} references undeclared assemblies: {:?}",
+                package.id,
+                package.version,
+                analysis.undeclared_references
+            );
+        }
+    }
+
+    // trusted uploaders skip review, unless the scan found something. modules have no dll to
+    // scan, so they are always reviewed
+    let approved = package.artifact.is_some()
+        && validate_permissions(&auser, Permission::APPROVE_MOD).await
+        && !analysis.is_risky();
+
+    let db_cata = match Categories::find()
+        .filter(entity::categories::Column::Name.eq(package.category.as_str()))
+        .one(&db.pool)
+        .await
+    {
+        Ok(Some(db_cata)) if !db_cata.retired => db_cata,
+        Ok(Some(_)) => {
+            return HttpResponse::BadRequest().body(format!(
+                "Category `{}` has been retired",
+                package.category
+            ))
+        }
+        Ok(None) => {
+            return HttpResponse::BadRequest()
+                .body(format!("Unknown category `{}`", package.category))
+        }
+        Err(e) => {
+            log::error!("failed to look up category: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
     };
 
-    let v_req = manifest.game_version.clone();
-    let vers = BeatSaberVersions::find()
+    let v_req = package.game_version.clone();
+    let vers = match BeatSaberVersions::find()
+        .filter(entity::beat_saber_versions::Column::Deprecated.eq(false))
         .all(&db.pool)
         .await
-        .unwrap()
+    {
+        Ok(vers) => vers,
+        Err(e) => {
+            log::error!("failed to look up game versions: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let vers = vers
         .into_iter()
-        .filter(|v| v_req.matches(&Version::parse(&v.ver).unwrap()))
+        .filter(|v| Version::parse(&v.ver).map_or(false, |ver| v_req.matches(&ver)))
         .collect::<Vec<_>>();
 
     if vers.len() == 0 {
-        return HttpResponse::BadRequest().body("Invalid game version");
+        return HttpResponse::BadRequest()
+            .body(format!("No supported game version matches `{}`", v_req));
     }
 
-    // see if mod exists; if it does add a new version; if it doesn't create a new mod
-    let mby_mod = Mods::find()
-        .filter(entity::mods::Column::Slug.eq(forgemod.manifest._id.clone()))
-        .one(&db.pool)
+    let staged = match web::block(move || cdn::StagedArtifact::stage(upload))
         .await
-        .unwrap();
-
-    let v_id;
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .and_then(|staged| staged)
+    {
+        Ok(staged) => staged,
+        Err(e) => {
+            log::error!("failed to stage upload: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
 
-    let trans = db.pool.begin().await.unwrap();
+    // artifact hashes are unique, the same file can't back two versions. the index catches
+    // concurrent uploads of the same file, this gives the usual answer
+    match Versions::find()
+        .filter(entity::versions::Column::ArtifactHash.eq(staged.hash.as_str()))
+        .one(&db.pool)
+        .await
+    {
+        Ok(None) => {}
+        Ok(Some(existing)) => {
+            staged.discard();
+            return validation::problems_response(vec![format!(
+                "This artifact was already uploaded as version {}",
+                existing.version
+            )]);
+        }
+        Err(e) => {
+            staged.discard();
+            log::error!("failed to look up artifact hash: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
 
-    if let Some(db_mod) = mby_mod {
-        let db_mod = db_mod.id;
-        for v in &vers {
-            let vm = entity::mod_beat_saber_versions::ActiveModel {
-                mod_id: Set(db_mod),
-                beat_saber_version_id: Set(v.id),
-            };
+    let new_version = NewVersion {
+        slug: package.id.clone(),
+        mod_type: package.mod_type,
+        name: package.name.clone(),
+        description: package.description.clone(),
+        website: Some(package.website.clone()).filter(|w| !w.is_empty()),
+        version: package.version.to_string(),
+        artifact_hash: staged.hash.clone(),
+        depends: package.depends.clone(),
+        conflicts: package.conflicts.clone(),
+        approved,
+        analysis,
+        release_notes,
+        long_description: readme,
+    };
 
-            //see if vm exists
-            if ModBeatSaberVersions::find()
-                .filter(entity::mod_beat_saber_versions::Column::ModId.eq(db_mod))
-                .filter(entity::mod_beat_saber_versions::Column::BeatSaberVersionId.eq(v.id))
-                .one(&trans)
-                .await
-                .unwrap()
-                .is_none()
-            {
-                vm.insert(&trans).await.unwrap();
-            }
+    let trans = match db.pool.begin().await {
+        Ok(trans) => trans,
+        Err(e) => {
+            staged.discard();
+            log::error!("failed to start upload transaction: {}", e);
+            return HttpResponse::InternalServerError().finish();
         }
+    };
 
-        let version_stats = entity::version_stats::ActiveModel {
-            ..Default::default()
+    let (db_mod, v_id) = match insert_version(&trans, &auser, &db_cata, &vers, &new_version).await {
+        Ok(inserted) => inserted,
+        Err(e) if is_duplicate_artifact(&e) => {
+            staged.discard();
+            return validation::problems_response(vec![
+                "This artifact was just uploaded as another version".to_string(),
+            ]);
         }
-        .insert(&trans)
-        .await
-        .unwrap()
-        .id;
-
-        let version = entity::versions::ActiveModel {
-            mod_id: Set(db_mod),
-            version: Set(manifest.version.clone().to_string()),
-            stats: Set(version_stats),
-            //todo: artifact hash
-            artifact_hash: Set("".to_string()),
-            //todo: download url
-            download_url: Set(format!(
-                "{}/cdn/{}@{}",
-                std::env::var("PUBLIC_URL").unwrap(),
-                forgemod.manifest._id,
-                manifest.version.clone().to_string()
-            )),
-            ..Default::default()
+        Err(e) => {
+            staged.discard();
+            log::error!("failed to store {}@{}: {}", new_version.slug, new_version.version, e);
+            return HttpResponse::InternalServerError().finish();
         }
-        .insert(&trans)
-        .await
-        .unwrap()
-        .id;
+    };
 
-        for v in &vers {
-            let _ = entity::version_beat_saber_versions::ActiveModel {
-                version_id: Set(version),
-                beat_saber_version_id: Set(v.id),
+    // follow-up work is queued with the version, so it runs exactly when the upload commits
+    let mut follow_up = vec![jobs::Job::IndexMod { mod_id: db_mod.id }];
+    if db_mod.icon.is_none() {
+        follow_up.push(jobs::Job::PackagedIcon {
+            mod_id: db_mod.id,
+            version_id: v_id,
+        });
+    }
+    for job in follow_up {
+        if let Err(e) = jobs::enqueue(&trans, job).await {
+            staged.discard();
+            log::error!("failed to queue jobs for {}@{}: {}", new_version.slug, new_version.version, e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    // the artifact is put in place before the commit, so a committed version always has one.
+    // a failed commit removes it again, a crash in between leaves an orphan for the reconciler
+    if let Err(e) = staged.publish(db_mod.id, v_id) {
+        log::error!("failed to publish {}@{}: {}", new_version.slug, new_version.version, e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    if let Err(e) = trans.commit().await {
+        let _ = std::fs::remove_file(cdn::artifact_path(db_mod.id, v_id));
+        log::error!("failed to commit {}@{}: {}", new_version.slug, new_version.version, e);
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::Created().json(json!({
+        "approved": new_version.approved,
+        "analysis": new_version.analysis,
+    }))
+}
+
+/// Whether `e` is the unique index on `versions.artifact_hash` turning down a second version
+/// of the same file.
+fn is_duplicate_artifact(e: &DbErr) -> bool {
+    matches!(
+        e.sql_err(),
+        Some(SqlErr::UniqueConstraintViolation(msg)) if msg.contains("idx_versions_artifact_hash")
+    )
+}
+
+/// A version taken from an uploaded forgemod.
+struct NewVersion {
+    slug: String,
+    mod_type: ModType,
+    name: String,
+    description: String,
+    website: Option<String>,
+    version: String,
+    artifact_hash: String,
+    /// `(mod id, version requirement)` of each dependency.
+    depends: Vec<(String, VersionReq)>,
+    conflicts: Vec<(String, VersionReq)>,
+    approved: bool,
+    analysis: AnalysisReport,
+    release_notes: Option<String>,
+    /// The package's README, which replaces the mod's long description.
+    long_description: Option<String>,
+}
+
+/// Stores a new version, creating its mod on the first upload.
+///
+/// Returns the mod and the id of the version.
+async fn insert_version<C: ConnectionTrait>(
+    db: &C,
+    author: &entity::users::Model,
+    category: &entity::categories::Model,
+    game_versions: &[entity::beat_saber_versions::Model],
+    new: &NewVersion,
+) -> Result<(entity::mods::Model, sea_orm::prelude::Uuid), DbErr> {
+    let mby_mod = Mods::find()
+        .filter(entity::mods::Column::Slug.eq(new.slug.as_str()))
+        .one(db)
+        .await?;
+
+    let db_mod = match mby_mod {
+        Some(db_mod) => match &new.long_description {
+            Some(readme) if db_mod.long_description.as_ref() != Some(readme) => {
+                let mut am = db_mod.into_active_model();
+                am.long_description = Set(Some(readme.clone()));
+                am.long_description_html = Set(Some(markdown::render(readme)));
+                am.update(db).await?
             }
-            .insert(&trans)
-            .await
-            .unwrap();
-        }
-
-        for conflict in manifest.conflicts {
-            let c_ver = Versions::find()
-                .filter(entity::versions::Column::ModId.eq(db_mod))
-                .all(&trans)
-                .await
-                .unwrap()
-                .into_iter()
-                .filter(|c| {
-                    conflict
-                        .version
-                        .matches(&Version::parse(&c.version).unwrap())
-                })
-                .collect::<Vec<_>>();
-
-            for c in c_ver {
-                let _ = entity::version_conflicts::ActiveModel {
-                    version_id: Set(version),
-                    dependent: Set(c.id),
-                }
-                .insert(&trans)
-                .await
-                .unwrap();
+            _ => db_mod,
+        },
+        None => {
+            let mod_stats = entity::mod_stats::ActiveModel {
+                ..Default::default()
             }
-        }
+            .insert(db)
+            .await?
+            .id;
 
-        for dependent in manifest.depends {
-            let d_ver = Versions::find()
-                .filter(entity::versions::Column::ModId.eq(db_mod))
-                .all(&trans)
-                .await
-                .unwrap()
-                .into_iter()
-                .filter(|d| {
-                    dependent
-                        .version
-                        .matches(&Version::parse(&d.version).unwrap())
-                })
-                .collect::<Vec<_>>();
-
-            for d in d_ver {
-                let _ = entity::version_dependents::ActiveModel {
-                    version_id: Set(version),
-                    dependent: Set(d.id),
-                }
-                .insert(&trans)
-                .await
-                .unwrap();
+            let db_mod = entity::mods::ActiveModel {
+                slug: Set(new.slug.clone()),
+                mod_type: Set(new.mod_type.name().to_string()),
+                name: Set(new.name.clone()),
+                author: Set(author.id),
+                description: Set(Some(new.description.clone())),
+                long_description: Set(new.long_description.clone()),
+                long_description_html: Set(new.long_description.as_deref().map(markdown::render)),
+                website: Set(new.website.clone()),
+                category: Set(category.id),
+                stats: Set(mod_stats),
+                ..Default::default()
             }
-        }
-        v_id = version;
-    } else {
-        let mod_stats = entity::mod_stats::ActiveModel {
-            ..Default::default()
-        }
-        .insert(&trans)
-        .await
-        .unwrap()
-        .id;
-
-        let db_mod = entity::mods::ActiveModel {
-            slug: Set(forgemod.manifest._id.clone()),
-            name: Set(manifest.name.clone()),
-            author: Set(auser.id),
-            description: Set(Some(manifest.description.clone())),
-            website: Set(Some(manifest.website.clone())),
-            category: Set(db_cata.id),
-            stats: Set(mod_stats),
-            ..Default::default()
-        }
-        .insert(&trans)
-        .await
-        .unwrap()
-        .id;
+            .insert(db)
+            .await?;
 
-        entity::user_mods::ActiveModel {
-            user_id: Set(auser.id),
-            mod_id: Set(db_mod),
+            entity::user_mods::ActiveModel {
+                user_id: Set(author.id),
+                mod_id: Set(db_mod.id),
+            }
+            .insert(db)
+            .await?;
+
+            entity::mod_categories::ActiveModel {
+                mod_id: Set(db_mod.id),
+                category_id: Set(category.id),
+            }
+            .insert(db)
+            .await?;
+
+            db_mod
         }
-        .insert(&trans)
-        .await
-        .unwrap();
+    };
 
-        for v in &vers {
-            let _ = entity::mod_beat_saber_versions::ActiveModel {
-                mod_id: Set(db_mod),
+    for v in game_versions {
+        if ModBeatSaberVersions::find()
+            .filter(entity::mod_beat_saber_versions::Column::ModId.eq(db_mod.id))
+            .filter(entity::mod_beat_saber_versions::Column::BeatSaberVersionId.eq(v.id))
+            .one(db)
+            .await?
+            .is_none()
+        {
+            entity::mod_beat_saber_versions::ActiveModel {
+                mod_id: Set(db_mod.id),
                 beat_saber_version_id: Set(v.id),
             }
-            .insert(&trans)
-            .await
-            .unwrap();
+            .insert(db)
+            .await?;
         }
+    }
 
-        let version_stats = entity::version_stats::ActiveModel {
-            ..Default::default()
-        }
-        .insert(&trans)
-        .await
-        .unwrap()
-        .id;
-
-        let version = entity::versions::ActiveModel {
-            mod_id: Set(db_mod),
-            version: Set(manifest.version.clone().to_string()),
-            stats: Set(version_stats),
-            //todo: artifact hash
-            artifact_hash: Set("".to_string()),
-            //todo: download url
-            download_url: Set(format!(
-                "{}/cdn/{}@{}",
-                std::env::var("PUBLIC_URL").unwrap(),
-                forgemod.manifest._id,
-                manifest.version.clone().to_string()
-            )),
-            ..Default::default()
+    let version_stats = entity::version_stats::ActiveModel {
+        ..Default::default()
+    }
+    .insert(db)
+    .await?
+    .id;
+
+    let version = entity::versions::ActiveModel {
+        mod_id: Set(db_mod.id),
+        version: Set(new.version.clone()),
+        stats: Set(version_stats),
+        artifact_hash: Set(new.artifact_hash.clone()),
+        approved: Set(new.approved),
+        analysis: Set(serde_json::to_value(&new.analysis).ok()),
+        risky: Set(new.analysis.is_risky()),
+        release_notes: Set(new.release_notes.clone()),
+        download_url: Set(format!(
+            "{}/cdn/{}@{}",
+            std::env::var("PUBLIC_URL").unwrap(),
+            new.slug,
+            new.version
+        )),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?
+    .id;
+
+    for v in game_versions {
+        entity::version_beat_saber_versions::ActiveModel {
+            version_id: Set(version),
+            beat_saber_version_id: Set(v.id),
         }
-        .insert(&trans)
-        .await
-        .unwrap()
-        .id;
+        .insert(db)
+        .await?;
+    }
+
+    entity::mod_versions::ActiveModel {
+        mod_id: Set(db_mod.id),
+        version_id: Set(version),
+    }
+    .insert(db)
+    .await?;
 
-        for v in &vers {
-            let _ = entity::version_beat_saber_versions::ActiveModel {
+    for (slug, req) in &new.conflicts {
+        for c in matching_versions(db, slug, req).await? {
+            entity::version_conflicts::ActiveModel {
                 version_id: Set(version),
-                beat_saber_version_id: Set(v.id),
+                dependent: Set(c.id),
             }
-            .insert(&trans)
-            .await
-            .unwrap();
+            .insert(db)
+            .await?;
         }
+    }
 
-        entity::mod_versions::ActiveModel {
-            mod_id: Set(db_mod),
-            version_id: Set(version),
-        }
-        .insert(&trans)
-        .await
-        .unwrap();
-
-        for conflict in manifest.conflicts {
-            let c_ver = Versions::find()
-                .filter(entity::versions::Column::ModId.eq(db_mod))
-                .all(&trans)
-                .await
-                .unwrap()
-                .into_iter()
-                .filter(|c| {
-                    conflict
-                        .version
-                        .matches(&Version::parse(&c.version).unwrap())
-                })
-                .collect::<Vec<_>>();
-
-            for c in c_ver {
-                let _ = entity::version_conflicts::ActiveModel {
-                    version_id: Set(version),
-                    dependent: Set(c.id),
-                }
-                .insert(&trans)
-                .await
-                .unwrap();
+    for (slug, req) in &new.depends {
+        for d in matching_versions(db, slug, req).await? {
+            entity::version_dependents::ActiveModel {
+                version_id: Set(version),
+                dependent: Set(d.id),
             }
+            .insert(db)
+            .await?;
         }
+    }
+
+    Ok((db_mod, version))
+}
+
+/// Versions of the mod `slug` that satisfy `req`.
+async fn matching_versions<C: ConnectionTrait>(
+    db: &C,
+    slug: &str,
+    req: &VersionReq,
+) -> Result<Vec<entity::versions::Model>, DbErr> {
+    let db_mod = match Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+    {
+        Some(db_mod) => db_mod,
+        None => return Ok(vec![]),
+    };
 
-        for dependent in manifest.depends {
-            let d_ver = Versions::find()
-                .filter(entity::versions::Column::ModId.eq(db_mod))
-                .all(&trans)
-                .await
-                .unwrap()
-                .into_iter()
-                .filter(|d| {
-                    dependent
-                        .version
-                        .matches(&Version::parse(&d.version).unwrap())
-                })
-                .collect::<Vec<_>>();
-
-            for d in d_ver {
-                let _ = entity::version_dependents::ActiveModel {
-                    version_id: Set(version),
-                    dependent: Set(d.id),
-                }
-                .insert(&trans)
-                .await
-                .unwrap();
+    Ok(Versions::find()
+        .filter(entity::versions::Column::ModId.eq(db_mod.id))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|v| Version::parse(&v.version).map_or(false, |v| req.matches(&v)))
+        .collect())
+}
+
+/// Assemblies an upload may reference besides the game's: those of the versions its
+/// dependencies resolve to, and the dlls it includes itself. `None` if a dependency version was
+/// uploaded before dlls were scanned, so what it ships is unknown.
+async fn declared_assemblies(
+    db: &DatabaseConnection,
+    depends: &[(String, VersionReq)],
+    includes: &[IncludeData],
+) -> Result<Option<Vec<String>>, DbErr> {
+    let mut declared = includes
+        .iter()
+        .filter_map(|i| {
+            let name = i.dest.rsplit(|c| c == '/' || c == '\\').next().unwrap_or_default();
+            let (stem, ext) = name.rsplit_once('.')?;
+            ext.eq_ignore_ascii_case("dll").then(|| stem.to_string())
+        })
+        .collect::<Vec<_>>();
+
+    for (slug, req) in depends {
+        for v in matching_versions(db, slug, req).await? {
+            let report = match v
+                .analysis
+                .and_then(|a| serde_json::from_value::<AnalysisReport>(a).ok())
+            {
+                Some(report) => report,
+                None => return Ok(None),
+            };
+            if let Some(assembly) = report.assembly {
+                declared.push(assembly);
             }
         }
-        v_id = version;
     }
 
-    let db_mod = Mods::find()
-        .filter(entity::mods::Column::Slug.eq(forgemod.manifest._id.clone()))
-        .one(&trans)
-        .await
-        .unwrap()
-        .unwrap();
+    Ok(Some(declared))
+}
 
-    let _ = std::fs::create_dir(format!("./data/cdn/{}", &db_mod.id));
-    std::fs::write(format!("./data/cdn/{}/{}.forgemod", &db_mod.id, v_id), buf).unwrap();
+/// Finds an `icon.png`, `icon.jpg` or `icon.webp` file among the files a package includes.
+pub(crate) fn packaged_icon(includes: &[IncludeData]) -> Option<Vec<u8>> {
+    includes
+        .iter()
+        .find(|i| {
+            let name = i
+                .dest
+                .rsplit(|c| c == '/' || c == '\\')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            matches!(
+                name.as_str(),
+                "icon.png" | "icon.jpg" | "icon.jpeg" | "icon.webp"
+            )
+        })
+        .map(|i| i.data.clone())
+}
 
-    trans.commit().await.unwrap();
+/// Reads a Markdown file such as `README.md` among the files a package includes.
+fn packaged_markdown(includes: &[IncludeData], file_name: &str) -> Option<String> {
+    includes
+        .iter()
+        .find(|i| {
+            i.dest
+                .rsplit(|c| c == '/' || c == '\\')
+                .next()
+                .unwrap_or_default()
+                .eq_ignore_ascii_case(file_name)
+        })
+        .and_then(|i| String::from_utf8(i.data.clone()).ok())
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+}
 
-    // add to meilisearch
-    let client = meilisearch_sdk::client::Client::new(
-        std::env::var("MEILI_URL").unwrap(),
-        Some(std::env::var("MEILI_KEY").unwrap()),
-    );
+#[post("/mods/{slug}/icon")]
+pub async fn upload_icon(
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    payload: web::Payload,
+    req: HttpRequest,
+) -> impl Responder {
+    upload_mod_image(db, path.into_inner(), payload, req, ImageKind::Icon).await
+}
 
-    let mod_vers = ModVersions::find()
-        .filter(entity::mod_versions::Column::ModId.eq(db_mod.id))
-        .find_also_related(Versions)
-        .all(&db.pool)
-        .await
-        .unwrap()
-        .into_iter()
-        .map(|(_, v)| Version::parse(&v.unwrap().version).unwrap())
-        .collect::<Vec<_>>();
+#[post("/mods/{slug}/cover")]
+pub async fn upload_cover(
+    db: web::Data<Database>,
+    path: web::Path<String>,
+    payload: web::Payload,
+    req: HttpRequest,
+) -> impl Responder {
+    upload_mod_image(db, path.into_inner(), payload, req, ImageKind::Cover).await
+}
 
-    let supported_versions = ModBeatSaberVersions::find().filter(entity::mod_beat_saber_versions::Column::ModId.eq(db_mod.id))
-        .find_also_related(BeatSaberVersions)
-        .all(&db.pool)
-        .await
-        .unwrap()
-        .into_iter()
-        .map(|(_, v)| Version::parse(&v.unwrap().ver).unwrap())
-        .collect::<Vec<_>>();
+async fn upload_mod_image(
+    db: web::Data<Database>,
+    slug: String,
+    payload: web::Payload,
+    req: HttpRequest,
+    kind: ImageKind,
+) -> HttpResponse {
+    let user = match get_user_from_request(&req, &db.pool).await {
+        Some(user) => user,
+        None => return HttpResponse::Unauthorized().body("Unauthorized"),
+    };
 
-    let mod_stats = ModStats::find_by_id(db_mod.stats)
+    let db_mod = match Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
         .one(&db.pool)
         .await
-        .unwrap()
-        .unwrap();
-
-    let meilimod = MeiliMod {
-        id: db_mod.id,
-        slug: db_mod.slug,
-        name: db_mod.name,
-        description: db_mod.description.unwrap_or("".to_string()),
-        category: db_cata.name,
-        author: MeiliUser {
-            username: auser.username.clone(),
-            display_name: auser.display_name.unwrap_or(auser.username),
-        },
-        stats: MeiliModStats {
-            downloads: mod_stats.downloads as u64,
-        },
-        versions: mod_vers
-            .into_iter()
-            .map(|v| MeiliVersion { version: v })
-            .collect(),
-        created_at: db_mod.created_at.and_utc().timestamp(),
-        updated_at: db_mod.updated_at.and_utc().timestamp(),
-        supported_versions,
+    {
+        Ok(Some(db_mod)) => db_mod,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            log::error!("failed to look up mod: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    if !can_edit_mod(&user, &db_mod).await {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    let buf = match cdn::read_limited(payload, images::MAX_IMAGE_SIZE).await {
+        Ok(buf) => buf,
+        Err(res) => return res,
     };
-    client.index(format!("{}mods", std::env::var("MEILI_PREFIX").unwrap_or("".to_string()))).add_or_replace(&[meilimod], None).await.unwrap();
 
-    HttpResponse::Created().finish()
+    match set_mod_image(&db.pool, db_mod, kind, buf).await {
+        Ok((url, thumbnail)) => HttpResponse::Ok().json(json!({ "url": url, "thumbnail": thumbnail })),
+        Err(UploadError::Image(e)) => HttpResponse::BadRequest().body(e.to_string()),
+        Err(e) => {
+            log::error!("failed to store {}: {}", kind.name(), e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Processes an icon or cover, stores it on the cdn and points the mod at it.
+pub(crate) async fn set_mod_image(
+    db: &DatabaseConnection,
+    db_mod: entity::mods::Model,
+    kind: ImageKind,
+    data: Vec<u8>,
+) -> Result<(String, String), UploadError> {
+    let img = cdn::store_upload(db_mod.id, kind, data).await?;
+
+    let mod_id = db_mod.id;
+    let mut am = db_mod.into_active_model();
+    match kind {
+        ImageKind::Cover => am.cover = Set(Some(img.url.clone())),
+        _ => am.icon = Set(Some(img.url.clone())),
+    }
+    am.updated_at = Set(Utc::now().naive_utc());
+    if let Err(e) = am.update(db).await {
+        // the mod still points at the old image, so that one stays
+        if let Err(e) = cdn::remove_image(mod_id, &img.stem) {
+            log::warn!("failed to remove unused {} of {}: {}", kind.name(), mod_id, e);
+        }
+        return Err(UploadError::Db(e));
+    }
+
+    if let Err(e) = cdn::prune_images(mod_id, kind, &img.stem) {
+        log::warn!("failed to remove replaced {} of {}: {}", kind.name(), mod_id, e);
+    }
+
+    if let Err(e) = search::index_mod(db, mod_id).await {
+        log::error!("failed to reindex mod {}: {}", mod_id, e);
+    }
+
+    Ok((img.url, img.thumbnail))
 }
@@ -0,0 +1,807 @@
+use std::fmt;
+
+use actix_web::{
+    cookie::{time, Cookie, SameSite},
+    http::StatusCode,
+    HttpResponse, ResponseError,
+};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+
+use crate::KEY;
+
+/// How long a user has to finish the authorization after requesting a `state`.
+const STATE_LIFETIME_MINUTES: i64 = 10;
+/// Cookie holding the nonce of the `state` the browser was sent off with.
+pub const STATE_COOKIE: &str = "oauth_state";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Provider {
+    Github,
+    Discord,
+    /// A generic OpenID Connect provider, configured through the `OIDC_*` variables.
+    Oidc,
+}
+
+impl Provider {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "github" => Some(Self::Github),
+            "discord" => Some(Self::Discord),
+            "oidc" => Some(Self::Oidc),
+            _ => None,
+        }
+    }
+
+    /// Name used in routes and stored in `user_identities.provider`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Github => "github",
+            Self::Discord => "discord",
+            Self::Oidc => "oidc",
+        }
+    }
+
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            Self::Github => "GITHUB",
+            Self::Discord => "DISCORD",
+            Self::Oidc => "OIDC",
+        }
+    }
+
+    /// Whether the provider has a profile bio worth keeping in sync.
+    pub fn has_bio(&self) -> bool {
+        matches!(self, Self::Github)
+    }
+
+    fn scope(&self) -> String {
+        match self {
+            // `user:email` is needed to read the address of users that keep their email private
+            Self::Github => "read:user user:email".to_string(),
+            Self::Discord => "identify email".to_string(),
+            Self::Oidc => std::env::var("OIDC_SCOPE").unwrap_or("openid profile email".to_string()),
+        }
+    }
+
+    fn client_id(&self) -> Result<String, OAuthError> {
+        self.env("CLIENT_ID")
+    }
+
+    fn client_secret(&self) -> Result<String, OAuthError> {
+        self.env("CLIENT_SECRET")
+    }
+
+    fn env(&self, name: &str) -> Result<String, OAuthError> {
+        let var = format!("{}_{}", self.env_prefix(), name);
+        std::env::var(&var).map_err(|_| OAuthError::Config(var))
+    }
+
+    /// Base URL of the provider's web flow, overridable with `GITHUB_BASE_URL` or
+    /// `DISCORD_BASE_URL` to test against a mock.
+    fn base_url(&self) -> String {
+        let default = match self {
+            Self::Github => "https://github.com",
+            Self::Discord => "https://discord.com",
+            Self::Oidc => "",
+        };
+        self.env("BASE_URL")
+            .unwrap_or(default.to_string())
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Base URL of the GitHub REST API, overridable with `GITHUB_API_URL`.
+    fn github_api_url(&self) -> String {
+        self.env("API_URL")
+            .unwrap_or("https://api.github.com".to_string())
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    fn authorize_endpoint(&self) -> Result<String, OAuthError> {
+        match self {
+            Self::Github => Ok(format!("{}/login/oauth/authorize", self.base_url())),
+            Self::Discord => Ok(format!("{}/oauth2/authorize", self.base_url())),
+            Self::Oidc => self.env("AUTHORIZE_URL"),
+        }
+    }
+
+    fn token_endpoint(&self) -> Result<String, OAuthError> {
+        match self {
+            Self::Github => Ok(format!("{}/login/oauth/access_token", self.base_url())),
+            Self::Discord => Ok(format!("{}/api/oauth2/token", self.base_url())),
+            Self::Oidc => self.env("TOKEN_URL"),
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Github => write!(f, "GitHub"),
+            Self::Discord => write!(f, "Discord"),
+            Self::Oidc => write!(
+                f,
+                "{}",
+                std::env::var("OIDC_NAME").unwrap_or("the identity provider".to_string())
+            ),
+        }
+    }
+}
+
+/// The account of a user at a provider, reduced to what we store.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub provider: Provider,
+    pub provider_user_id: String,
+    pub username: String,
+    /// Always a verified address.
+    pub email: String,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum OAuthError {
+    UnknownProvider(String),
+    /// A required environment variable is not set.
+    Config(String),
+    /// The `state` is missing, forged, expired, issued for another provider or to another browser.
+    InvalidState,
+    /// The provider rejected the code, e.g. because it was already used or the app was revoked.
+    Rejected(Provider, String),
+    /// The provider refused the request because of rate limiting.
+    RateLimited(Provider),
+    /// The provider could not be reached or answered with something unexpected.
+    Upstream(Provider, String),
+    /// The account has no verified email address.
+    NoEmail(Provider),
+    /// Logging in would create an account with an email another account already uses.
+    EmailInUse(Provider),
+    /// Every username the account could get is already taken.
+    NoUsername(Provider),
+    /// The identity belongs to another account.
+    AlreadyLinked(Provider),
+    /// The account already has an identity at this provider.
+    ProviderLinked(Provider),
+    NotLinked(Provider),
+    /// Unlinking would leave the account without a way to log in.
+    LastIdentity,
+    Unauthorized,
+    Db(sea_orm::DbErr),
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownProvider(p) => write!(f, "Unknown login provider `{}`", p),
+            Self::Config(var) => write!(f, "Login provider is not configured ({} is not set)", var),
+            Self::InvalidState => write!(f, "Invalid or expired OAuth state"),
+            Self::Rejected(p, e) => write!(f, "{} rejected the login: {}", p, e),
+            Self::RateLimited(p) => write!(f, "{} is rate limiting requests, try again later", p),
+            Self::Upstream(p, e) => write!(f, "{} request failed: {}", p, e),
+            Self::NoEmail(p) => write!(f, "Your {} account has no verified email address", p),
+            Self::EmailInUse(p) => write!(
+                f,
+                "An account with the email of your {} account already exists, log in to it and link {} instead",
+                p, p
+            ),
+            Self::NoUsername(p) => write!(
+                f,
+                "No free username could be found for your {} account, rename it and try again",
+                p
+            ),
+            Self::AlreadyLinked(p) => write!(f, "This {} account is linked to another user", p),
+            Self::ProviderLinked(p) => write!(f, "A {} account is already linked", p),
+            Self::NotLinked(p) => write!(f, "No {} account is linked", p),
+            Self::LastIdentity => write!(f, "The last linked account can not be unlinked"),
+            Self::Unauthorized => write!(f, "Unauthorized"),
+            Self::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for OAuthError {}
+
+impl From<sea_orm::DbErr> for OAuthError {
+    fn from(e: sea_orm::DbErr) -> Self {
+        Self::Db(e)
+    }
+}
+
+impl OAuthError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownProvider(_) => "unknown_provider",
+            Self::Config(_) => "not_configured",
+            Self::InvalidState => "invalid_state",
+            Self::Rejected(..) => "rejected",
+            Self::RateLimited(_) => "rate_limited",
+            Self::Upstream(..) => "upstream_error",
+            Self::NoEmail(_) => "no_email",
+            Self::EmailInUse(_) => "email_in_use",
+            Self::NoUsername(_) => "no_username",
+            Self::AlreadyLinked(_) => "already_linked",
+            Self::ProviderLinked(_) => "provider_linked",
+            Self::NotLinked(_) => "not_linked",
+            Self::LastIdentity => "last_identity",
+            Self::Unauthorized => "unauthorized",
+            Self::Db(_) => "internal_error",
+        }
+    }
+}
+
+impl ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownProvider(_) | Self::NotLinked(_) => StatusCode::NOT_FOUND,
+            Self::InvalidState | Self::Rejected(..) | Self::NoEmail(_) | Self::LastIdentity => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::EmailInUse(_)
+            | Self::NoUsername(_)
+            | Self::AlreadyLinked(_)
+            | Self::ProviderLinked(_) => StatusCode::CONFLICT,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::RateLimited(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Upstream(..) => StatusCode::BAD_GATEWAY,
+            Self::Config(_) | Self::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let Self::Db(e) = self {
+            log::error!("oauth login failed: {}", e);
+        }
+
+        HttpResponse::build(self.status_code()).json(json!({
+            "error": self.code(),
+            "message": self.to_string(),
+        }))
+    }
+}
+
+/// Signed, short lived `state` parameter. Being signed with the server key means it can be
+/// checked without keeping track of issued values. The redirect uri rides along because
+/// token requests have to repeat it. The nonce is also put in a cookie, tying the `state` to
+/// the browser that started the login.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthState {
+    pub provider: String,
+    pub redirect_uri: Option<String>,
+    nonce: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    exp: DateTime<Utc>,
+}
+
+/// Returns the encoded `state` and its nonce.
+fn new_state(provider: Provider, redirect_uri: Option<String>) -> (String, String) {
+    let nonce: [u8; 16] = rand::thread_rng().gen();
+    let nonce: String = nonce.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let state = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &OAuthState {
+            provider: provider.name().to_string(),
+            redirect_uri,
+            nonce: nonce.clone(),
+            exp: Utc::now() + chrono::Duration::minutes(STATE_LIFETIME_MINUTES),
+        },
+        &jsonwebtoken::EncodingKey::from_secret(&KEY.0),
+    )
+    .unwrap();
+
+    (state, nonce)
+}
+
+/// Checks `state` and that `nonce`, read from the [`STATE_COOKIE`], is the one it was issued with.
+pub fn verify_state(
+    provider: Provider,
+    state: &str,
+    nonce: Option<&str>,
+) -> Result<OAuthState, OAuthError> {
+    let state = jsonwebtoken::decode::<OAuthState>(
+        state,
+        &jsonwebtoken::DecodingKey::from_secret(&KEY.0),
+        &jsonwebtoken::Validation::default(),
+    )
+    .map_err(|_| OAuthError::InvalidState)?
+    .claims;
+
+    if state.provider != provider.name() || nonce != Some(state.nonce.as_str()) {
+        return Err(OAuthError::InvalidState);
+    }
+    Ok(state)
+}
+
+/// The [`STATE_COOKIE`] for `nonce`. Only sent back to the auth routes and hidden from scripts.
+pub fn state_cookie(nonce: String) -> Cookie<'static> {
+    Cookie::build(STATE_COOKIE, nonce)
+        .path("/auth")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::minutes(STATE_LIFETIME_MINUTES))
+        .finish()
+}
+
+/// URL of the provider's authorization page for a fresh `state`, and the nonce of that state to
+/// put in the [`state_cookie`].
+pub fn authorize_url(
+    provider: Provider,
+    redirect_uri: Option<String>,
+) -> Result<(String, String), OAuthError> {
+    let mut url = format!(
+        "{}?response_type=code&client_id={}&scope={}",
+        provider.authorize_endpoint()?,
+        urlencode(&provider.client_id()?),
+        urlencode(&provider.scope()),
+    );
+    if let Some(redirect_uri) = &redirect_uri {
+        url.push_str(&format!("&redirect_uri={}", urlencode(redirect_uri)));
+    }
+    let (state, nonce) = new_state(provider, redirect_uri);
+    url.push_str(&format!("&state={}", urlencode(&state)));
+    Ok((url, nonce))
+}
+
+fn urlencode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GithubUser {
+    avatar_url: Option<String>,
+    bio: Option<String>,
+    email: Option<String>,
+    id: i64,
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct GithubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+#[derive(Deserialize)]
+struct DiscordUser {
+    id: String,
+    username: String,
+    avatar: Option<String>,
+    email: Option<String>,
+    verified: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct OidcUserInfo {
+    sub: String,
+    preferred_username: Option<String>,
+    name: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    picture: Option<String>,
+}
+
+fn check_status(provider: Provider, res: &minreq::Response) -> Result<(), OAuthError> {
+    match res.status_code {
+        200..=299 => Ok(()),
+        401 => Err(OAuthError::Rejected(
+            provider,
+            "access token was not accepted".to_string(),
+        )),
+        429 => Err(OAuthError::RateLimited(provider)),
+        403 if res.headers.get("x-ratelimit-remaining").map(|r| r.as_str()) == Some("0") => {
+            Err(OAuthError::RateLimited(provider))
+        }
+        code => Err(OAuthError::Upstream(
+            provider,
+            format!("unexpected status {} {}", code, res.reason_phrase),
+        )),
+    }
+}
+
+fn parse<T: DeserializeOwned>(provider: Provider, res: &minreq::Response) -> Result<T, OAuthError> {
+    res.json::<T>()
+        .map_err(|e| OAuthError::Upstream(provider, format!("invalid response: {}", e)))
+}
+
+fn api_get<T: DeserializeOwned>(provider: Provider, url: String, token: &str) -> Result<T, OAuthError> {
+    let res = minreq::get(url)
+        .with_header("User-Agent", "forge-registry")
+        .with_header("Accept", "application/json")
+        .with_header("Authorization", format!("Bearer {}", token))
+        .send()
+        .map_err(|e| OAuthError::Upstream(provider, e.to_string()))?;
+
+    check_status(provider, &res)?;
+    parse(provider, &res)
+}
+
+fn exchange_code(
+    provider: Provider,
+    code: &str,
+    redirect_uri: Option<&str>,
+) -> Result<String, OAuthError> {
+    let mut form = vec![
+        ("grant_type", "authorization_code".to_string()),
+        ("code", code.to_string()),
+        ("client_id", provider.client_id()?),
+        ("client_secret", provider.client_secret()?),
+    ];
+    if let Some(redirect_uri) = redirect_uri {
+        form.push(("redirect_uri", redirect_uri.to_string()));
+    }
+    let body = form
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, urlencode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let res = minreq::post(provider.token_endpoint()?)
+        .with_header("User-Agent", "forge-registry")
+        .with_header("Accept", "application/json")
+        .with_header("Content-Type", "application/x-www-form-urlencoded")
+        .with_body(body)
+        .send()
+        .map_err(|e| OAuthError::Upstream(provider, e.to_string()))?;
+
+    // standard providers answer a bad code with 400, github with 200 and an `error` field
+    if res.status_code != 400 {
+        check_status(provider, &res)?;
+    }
+
+    let token = parse::<TokenResponse>(provider, &res)?;
+    match (token.access_token, token.error) {
+        (Some(access_token), None) => Ok(access_token),
+        (_, Some(error)) => Err(OAuthError::Rejected(
+            provider,
+            token.error_description.unwrap_or(error),
+        )),
+        (None, None) => Err(OAuthError::Upstream(
+            provider,
+            "no access token in response".to_string(),
+        )),
+    }
+}
+
+fn fetch_github(token: &str) -> Result<Identity, OAuthError> {
+    let provider = Provider::Github;
+    let api = provider.github_api_url();
+    let user = api_get::<GithubUser>(provider, format!("{}/user", api), token)?;
+
+    // the profile only includes the email if the user made it public
+    let email = match user.email {
+        Some(email) => email,
+        None => api_get::<Vec<GithubEmail>>(provider, format!("{}/user/emails", api), token)?
+            .into_iter()
+            .filter(|e| e.verified)
+            .max_by_key(|e| e.primary)
+            .map(|e| e.email)
+            .ok_or(OAuthError::NoEmail(provider))?,
+    };
+
+    Ok(Identity {
+        provider,
+        provider_user_id: user.id.to_string(),
+        username: user.login,
+        email,
+        avatar_url: user.avatar_url,
+        bio: user.bio,
+    })
+}
+
+fn fetch_discord(token: &str) -> Result<Identity, OAuthError> {
+    let provider = Provider::Discord;
+    let user = api_get::<DiscordUser>(
+        provider,
+        format!("{}/api/users/@me", provider.base_url()),
+        token,
+    )?;
+
+    let email = match (user.email, user.verified) {
+        (Some(email), Some(true)) => email,
+        _ => return Err(OAuthError::NoEmail(provider)),
+    };
+
+    Ok(Identity {
+        provider,
+        avatar_url: user.avatar.map(|hash| {
+            format!("https://cdn.discordapp.com/avatars/{}/{}.png", user.id, hash)
+        }),
+        provider_user_id: user.id,
+        username: user.username,
+        email,
+        bio: None,
+    })
+}
+
+fn fetch_oidc(token: &str) -> Result<Identity, OAuthError> {
+    let provider = Provider::Oidc;
+    let info = api_get::<OidcUserInfo>(provider, provider.env("USERINFO_URL")?, token)?;
+
+    // an address the provider doesn't vouch for could belong to someone else's account here
+    let email = match (info.email, info.email_verified) {
+        (Some(email), Some(true)) => email,
+        _ => return Err(OAuthError::NoEmail(provider)),
+    };
+
+    Ok(Identity {
+        provider,
+        username: info
+            .preferred_username
+            .or(info.name)
+            .unwrap_or(info.sub.clone()),
+        provider_user_id: info.sub,
+        email,
+        avatar_url: info.picture,
+        bio: None,
+    })
+}
+
+/// Exchanges an authorization code for the identity of the user at `provider`.
+///
+/// This blocks on network requests, so it should be run through `web::block`.
+pub fn fetch_identity(
+    provider: Provider,
+    code: &str,
+    redirect_uri: Option<&str>,
+) -> Result<Identity, OAuthError> {
+    let token = exchange_code(provider, code, redirect_uri)?;
+
+    match provider {
+        Provider::Github => fetch_github(&token),
+        Provider::Discord => fetch_discord(&token),
+        Provider::Oidc => fetch_oidc(&token),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Mutex, MutexGuard},
+    };
+
+    use super::*;
+
+    /// The providers are configured through the environment, which all tests share.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Points GitHub at a local server answering each path in `routes` with a status and a JSON
+    /// body. Holds the environment until the guard is dropped.
+    fn mock_github(routes: Vec<(&'static str, u16, &'static str)>) -> MutexGuard<'static, ()> {
+        let guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line.split(' ').nth(1).unwrap_or_default().to_string();
+
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                reader.read_exact(&mut vec![0; content_length]).unwrap();
+
+                let (status, body) = routes
+                    .iter()
+                    .find(|(p, _, _)| *p == path)
+                    .map_or((404, "{}"), |(_, status, body)| (*status, *body));
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        std::env::set_var("GITHUB_BASE_URL", &url);
+        std::env::set_var("GITHUB_API_URL", &url);
+        std::env::set_var("GITHUB_CLIENT_ID", "client");
+        std::env::set_var("GITHUB_CLIENT_SECRET", "secret");
+        guard
+    }
+
+    fn encode(state: &OAuthState) -> String {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            state,
+            &jsonwebtoken::EncodingKey::from_secret(&KEY.0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn accepts_state_with_its_nonce() {
+        let (state, nonce) = new_state(Provider::Github, Some("https://example.com".to_string()));
+
+        let state = verify_state(Provider::Github, &state, Some(&nonce)).unwrap();
+        assert_eq!(state.redirect_uri.as_deref(), Some("https://example.com"));
+    }
+
+    #[test]
+    fn rejects_state_of_another_provider() {
+        let (state, nonce) = new_state(Provider::Github, None);
+
+        assert!(matches!(
+            verify_state(Provider::Discord, &state, Some(&nonce)),
+            Err(OAuthError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn rejects_state_without_its_nonce() {
+        let (state, _) = new_state(Provider::Github, None);
+        let (_, other) = new_state(Provider::Github, None);
+
+        assert!(matches!(
+            verify_state(Provider::Github, &state, None),
+            Err(OAuthError::InvalidState)
+        ));
+        assert!(matches!(
+            verify_state(Provider::Github, &state, Some(&other)),
+            Err(OAuthError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn rejects_expired_state() {
+        let state = encode(&OAuthState {
+            provider: Provider::Github.name().to_string(),
+            redirect_uri: None,
+            nonce: "nonce".to_string(),
+            exp: Utc::now() - chrono::Duration::minutes(STATE_LIFETIME_MINUTES),
+        });
+
+        assert!(matches!(
+            verify_state(Provider::Github, &state, Some("nonce")),
+            Err(OAuthError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn rejects_forged_state() {
+        let state = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &OAuthState {
+                provider: Provider::Github.name().to_string(),
+                redirect_uri: None,
+                nonce: "nonce".to_string(),
+                exp: Utc::now() + chrono::Duration::minutes(STATE_LIFETIME_MINUTES),
+            },
+            &jsonwebtoken::EncodingKey::from_secret(b"not the server key"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            verify_state(Provider::Github, &state, Some("nonce")),
+            Err(OAuthError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn reports_rejected_code() {
+        // github answers a bad code with 200 and an `error` field
+        let _env = mock_github(vec![(
+            "/login/oauth/access_token",
+            200,
+            r#"{"error":"bad_verification_code","error_description":"The code passed is incorrect or expired."}"#,
+        )]);
+
+        match exchange_code(Provider::Github, "code", None) {
+            Err(OAuthError::Rejected(Provider::Github, e)) => {
+                assert_eq!(e, "The code passed is incorrect or expired.")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_rejected_code_with_400() {
+        let _env = mock_github(vec![(
+            "/login/oauth/access_token",
+            400,
+            r#"{"error":"invalid_grant"}"#,
+        )]);
+
+        match exchange_code(Provider::Github, "code", None) {
+            Err(OAuthError::Rejected(Provider::Github, e)) => assert_eq!(e, "invalid_grant"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn exchanges_code_for_token() {
+        let _env = mock_github(vec![(
+            "/login/oauth/access_token",
+            200,
+            r#"{"access_token":"token","token_type":"bearer"}"#,
+        )]);
+
+        assert_eq!(exchange_code(Provider::Github, "code", None).unwrap(), "token");
+    }
+
+    #[test]
+    fn falls_back_to_primary_verified_email() {
+        let _env = mock_github(vec![
+            (
+                "/user",
+                200,
+                r#"{"id":1,"login":"octocat","email":null,"avatar_url":null,"bio":null}"#,
+            ),
+            (
+                "/user/emails",
+                200,
+                r#"[
+                    {"email":"old@example.com","primary":false,"verified":true},
+                    {"email":"unverified@example.com","primary":false,"verified":false},
+                    {"email":"octocat@example.com","primary":true,"verified":true}
+                ]"#,
+            ),
+        ]);
+
+        let identity = fetch_github("token").unwrap();
+        assert_eq!(identity.email, "octocat@example.com");
+        assert_eq!(identity.username, "octocat");
+        assert_eq!(identity.provider_user_id, "1");
+    }
+
+    #[test]
+    fn rejects_accounts_without_verified_email() {
+        let _env = mock_github(vec![
+            (
+                "/user",
+                200,
+                r#"{"id":1,"login":"octocat","email":null,"avatar_url":null,"bio":null}"#,
+            ),
+            (
+                "/user/emails",
+                200,
+                r#"[{"email":"octocat@example.com","primary":true,"verified":false}]"#,
+            ),
+        ]);
+
+        assert!(matches!(
+            fetch_github("token"),
+            Err(OAuthError::NoEmail(Provider::Github))
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::Read;
+
+use forge_lib::structs::v1::{unpack_v1_forgemod, ForgeModTypes, IncludeData};
+use juniper::GraphQLEnum;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+/// The kinds of package the forge format defines.
+#[derive(GraphQLEnum, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModType {
+    /// A mod players install, with a dll the game loads.
+    Mod,
+    /// A dll other mods build on.
+    Lib,
+    /// Files installed as they are, without a dll of their own.
+    Module,
+}
+
+impl ModType {
+    /// The name stored in `mods.mod_type`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mod => "mod",
+            Self::Lib => "lib",
+            Self::Module => "module",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mod" => Some(Self::Mod),
+            "lib" => Some(Self::Lib),
+            "module" => Some(Self::Module),
+            _ => None,
+        }
+    }
+}
+
+/// An unpacked forgemod, whatever kind of package it is.
+pub struct Package {
+    pub mod_type: ModType,
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub website: String,
+    pub version: Version,
+    pub game_version: VersionReq,
+    pub category: String,
+    /// `(mod id, version requirement)` of each dependency.
+    pub depends: Vec<(String, VersionReq)>,
+    pub conflicts: Vec<(String, VersionReq)>,
+    /// The dll of mods and libs. Modules have none.
+    pub artifact: Option<Vec<u8>>,
+    pub includes: Vec<IncludeData>,
+}
+
+/// Every package kind carries the same manifest, only their data differs.
+macro_rules! package {
+    ($fm:ident, $mod_type:expr, $artifact:expr) => {
+        Package {
+            mod_type: $mod_type,
+            id: $fm.manifest._id,
+            name: $fm.manifest.inner.name,
+            description: $fm.manifest.inner.description,
+            website: $fm.manifest.inner.website,
+            version: $fm.manifest.inner.version,
+            game_version: $fm.manifest.inner.game_version,
+            category: $fm.manifest.inner.category.to_string(),
+            depends: $fm
+                .manifest
+                .inner
+                .depends
+                .into_iter()
+                .map(|d| (d.id, d.version))
+                .collect(),
+            conflicts: $fm
+                .manifest
+                .inner
+                .conflicts
+                .into_iter()
+                .map(|c| (c.id, c.version))
+                .collect(),
+            includes: $fm.data.includes,
+            artifact: $artifact,
+        }
+    };
+}
+
+/// Unpacks a forgemod. `None` means the file is not a forgemod.
+pub fn unpack(file: impl Read) -> Option<Package> {
+    match unpack_v1_forgemod(file).ok()? {
+        ForgeModTypes::Mod(m) => Some(package!(m, ModType::Mod, Some(m.data.artifact_data))),
+        ForgeModTypes::Lib(l) => Some(package!(l, ModType::Lib, Some(l.data.artifact_data))),
+        ForgeModTypes::Module(m) => Some(package!(m, ModType::Module, None)),
+    }
+}
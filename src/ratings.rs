@@ -0,0 +1,330 @@
+use chrono::{DateTime, Utc};
+use entity::prelude::*;
+use juniper::{graphql_value, FieldError, FieldResult, GraphQLObject};
+use sea_orm::{
+    prelude::Uuid, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, IntoActiveModel, ModelTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+    Statement, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::can_edit_mod, jobs, versions};
+
+const MIN_RATING: i32 = 1;
+const MAX_RATING: i32 = 5;
+const MAX_REVIEW_LEN: usize = 5000;
+const MAX_REPLY_LEN: usize = 2000;
+
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GRating {
+    pub id: uuid::Uuid,
+    /// From 1 to 5.
+    pub rating: i32,
+    pub review: Option<String>,
+    /// The version the reviewer used, if they said.
+    pub version: Option<String>,
+    /// Username of the reviewer.
+    pub reviewer: String,
+    /// The mod author's answer to the review.
+    pub reply: Option<String>,
+    pub replied_at: Option<DateTime<Utc>>,
+    /// Hidden reviews still count towards the rating, but aren't listed.
+    pub hidden: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl GRating {
+    async fn from_db_rating(
+        db: &DatabaseConnection,
+        r: entity::mod_ratings::Model,
+    ) -> Result<Self, DbErr> {
+        let reviewer = Users::find_by_id(r.user_id)
+            .one(db)
+            .await?
+            .map(|u| u.username)
+            .unwrap_or_default();
+        let version = match r.version_id {
+            Some(id) => Versions::find_by_id(id).one(db).await?.map(|v| v.version),
+            None => None,
+        };
+
+        Ok(GRating {
+            id: uuid::Uuid::from_bytes(*r.id.as_bytes()),
+            rating: r.rating,
+            review: r.review,
+            version,
+            reviewer,
+            reply: r.reply,
+            replied_at: r.replied_at.map(|t| t.and_utc()),
+            hidden: r.hidden,
+            created_at: r.created_at.and_utc(),
+            updated_at: r.updated_at.and_utc(),
+        })
+    }
+}
+
+/// Trims `text`, treating an empty one as none, and checks its length.
+fn clean_text(text: Option<String>, max: usize, field: &str) -> FieldResult<Option<String>> {
+    let text = text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    if text.as_ref().is_some_and(|t| t.chars().count() > max) {
+        return Err(FieldError::new(
+            format!("The {} must be at most {} characters", field, max),
+            graphql_value!({ "text": "Text is too long" }),
+        ));
+    }
+    Ok(text)
+}
+
+async fn find_mod(db: &DatabaseConnection, slug: String) -> FieldResult<entity::mods::Model> {
+    Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Mod not found",
+                graphql_value!({ "internal_error": "Mod not found" }),
+            )
+        })
+}
+
+async fn find_rating(
+    db: &DatabaseConnection,
+    id: uuid::Uuid,
+) -> FieldResult<entity::mod_ratings::Model> {
+    ModRatings::find_by_id(Uuid::from_bytes(*id.as_bytes()))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Rating not found",
+                graphql_value!({ "notFound": "Rating not found" }),
+            )
+        })
+}
+
+/// Recomputes the rating of a mod, and of the versions its ratings were about.
+async fn refresh_stats<C: ConnectionTrait>(
+    db: &C,
+    mod_id: Uuid,
+    version_ids: &[Option<Uuid>],
+) -> Result<(), DbErr> {
+    db.execute(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        r#"UPDATE mod_stats
+           SET rating = COALESCE(r.rating, 0), rating_count = r.rating_count
+           FROM (
+               SELECT AVG(rating)::float8 AS rating, COUNT(*)::int AS rating_count
+               FROM mod_ratings WHERE mod_id = $1
+           ) r
+           WHERE mod_stats.id = (SELECT stats FROM mods WHERE id = $1)"#,
+        [mod_id.into()],
+    ))
+    .await?;
+
+    for version_id in version_ids.iter().flatten() {
+        db.execute(Statement::from_sql_and_values(
+            db.get_database_backend(),
+            r#"UPDATE version_stats
+               SET rating = COALESCE(r.rating, 0), rating_count = r.rating_count
+               FROM (
+                   SELECT AVG(rating)::float8 AS rating, COUNT(*)::int AS rating_count
+                   FROM mod_ratings WHERE version_id = $1
+               ) r
+               WHERE version_stats.id = (SELECT stats FROM versions WHERE id = $1)"#,
+            [(*version_id).into()],
+        ))
+        .await?;
+    }
+
+    jobs::enqueue(db, jobs::Job::IndexMod { mod_id }).await
+}
+
+/// Rates a mod, optionally with a review of the version the user used. Rating the same mod
+/// again replaces the earlier rating and review.
+pub async fn rate(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    rating: i32,
+    review: Option<String>,
+    version: Option<String>,
+) -> FieldResult<GRating> {
+    if !(MIN_RATING..=MAX_RATING).contains(&rating) {
+        return Err(FieldError::new(
+            format!("Rating must be from {} to {}", MIN_RATING, MAX_RATING),
+            graphql_value!({ "rating": "Rating is out of range" }),
+        ));
+    }
+    let review = clean_text(review, MAX_REVIEW_LEN, "review")?;
+
+    let (m, version_id) = match version {
+        Some(version) => {
+            let (m, v) = versions::find_version(db, slug, version).await?;
+            (m, Some(v.id))
+        }
+        None => (find_mod(db, slug).await?, None),
+    };
+
+    if m.author == user.id {
+        return Err(FieldError::new(
+            "You can't rate your own mod",
+            graphql_value!({ "auth": "You can't rate your own mod" }),
+        ));
+    }
+
+    let existing = ModRatings::find()
+        .filter(entity::mod_ratings::Column::ModId.eq(m.id))
+        .filter(entity::mod_ratings::Column::UserId.eq(user.id))
+        .one(db)
+        .await?;
+
+    let trans = db.begin().await?;
+
+    let (rating, previous_version) = match existing {
+        Some(existing) => {
+            let previous_version = existing.version_id;
+            // a reply answers the review it was written for
+            let clear_reply = existing.review != review;
+            let mut am = existing.into_active_model();
+            am.rating = Set(rating);
+            am.review = Set(review);
+            if clear_reply {
+                am.reply = Set(None);
+                am.replied_at = Set(None);
+            }
+            am.version_id = Set(version_id);
+            am.updated_at = Set(Utc::now().naive_utc());
+            (am.update(&trans).await?, previous_version)
+        }
+        None => {
+            let rating = entity::mod_ratings::ActiveModel {
+                mod_id: Set(m.id),
+                user_id: Set(user.id),
+                version_id: Set(version_id),
+                rating: Set(rating),
+                review: Set(review),
+                ..Default::default()
+            }
+            .insert(&trans)
+            .await?;
+            (rating, None)
+        }
+    };
+
+    refresh_stats(&trans, m.id, &[version_id, previous_version]).await?;
+
+    trans.commit().await?;
+
+    Ok(GRating::from_db_rating(db, rating).await?)
+}
+
+/// Takes back the user's rating of a mod.
+pub async fn remove(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+) -> FieldResult<bool> {
+    let m = find_mod(db, slug).await?;
+
+    let rating = match ModRatings::find()
+        .filter(entity::mod_ratings::Column::ModId.eq(m.id))
+        .filter(entity::mod_ratings::Column::UserId.eq(user.id))
+        .one(db)
+        .await?
+    {
+        Some(rating) => rating,
+        None => return Ok(false),
+    };
+
+    let trans = db.begin().await?;
+
+    let version_id = rating.version_id;
+    rating.delete(&trans).await?;
+    refresh_stats(&trans, m.id, &[version_id]).await?;
+
+    trans.commit().await?;
+
+    Ok(true)
+}
+
+/// Lists the reviews of a mod, newest first. Ratings without a review and hidden reviews are
+/// left out.
+pub async fn find_reviews(
+    db: &DatabaseConnection,
+    slug: String,
+    limit: i32,
+    offset: i32,
+) -> FieldResult<Vec<GRating>> {
+    let m = find_mod(db, slug).await?;
+
+    let ratings = ModRatings::find()
+        .filter(entity::mod_ratings::Column::ModId.eq(m.id))
+        .filter(entity::mod_ratings::Column::Review.is_not_null())
+        .filter(entity::mod_ratings::Column::Hidden.eq(false))
+        .order_by_desc(entity::mod_ratings::Column::UpdatedAt)
+        .order_by_asc(entity::mod_ratings::Column::Id)
+        .limit(limit.max(0) as u64)
+        .offset(offset.max(0) as u64)
+        .all(db)
+        .await?;
+
+    let mut r = vec![];
+    for rating in ratings {
+        r.push(GRating::from_db_rating(db, rating).await?);
+    }
+    Ok(r)
+}
+
+/// Sets the mod author's reply to a review. `None` removes the reply.
+pub async fn reply(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    id: uuid::Uuid,
+    reply: Option<String>,
+) -> FieldResult<GRating> {
+    let rating = find_rating(db, id).await?;
+    let m = Mods::find_by_id(rating.mod_id).one(db).await?.ok_or_else(|| {
+        FieldError::new(
+            "Mod not found",
+            graphql_value!({ "internal_error": "Mod not found" }),
+        )
+    })?;
+
+    if !can_edit_mod(user, &m).await {
+        return Err(FieldError::new(
+            "Unauthorized",
+            graphql_value!({ "auth": "Unauthorized" }),
+        ));
+    }
+    if rating.review.is_none() {
+        return Err(FieldError::new(
+            "Only reviews can be replied to",
+            graphql_value!({ "reply": "The rating has no review" }),
+        ));
+    }
+
+    let reply = clean_text(reply, MAX_REPLY_LEN, "reply")?;
+
+    let mut am = rating.into_active_model();
+    am.replied_at = Set(reply.as_ref().map(|_| Utc::now().naive_utc()));
+    am.reply = Set(reply);
+
+    Ok(GRating::from_db_rating(db, am.update(db).await?).await?)
+}
+
+/// Hides or shows a review. Hiding keeps the rating, so moderators can take down abusive text
+/// without changing the mod's score.
+pub async fn set_hidden(
+    db: &DatabaseConnection,
+    id: uuid::Uuid,
+    hidden: bool,
+) -> FieldResult<GRating> {
+    let rating = find_rating(db, id).await?;
+
+    let mut am = rating.into_active_model();
+    am.hidden = Set(hidden);
+
+    Ok(GRating::from_db_rating(db, am.update(db).await?).await?)
+}
@@ -0,0 +1,246 @@
+use std::{collections::HashSet, fmt, path::PathBuf, time::Duration};
+
+use actix_web::web;
+use entity::prelude::*;
+use sea_orm::{prelude::Uuid, DatabaseConnection, DbErr, EntityTrait};
+
+use crate::{cdn, search};
+
+/// Files younger than this may belong to an upload that is still in progress.
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+pub enum ReconcileError {
+    Db(DbErr),
+    Io(std::io::Error),
+    Search(search::SearchError),
+}
+
+impl fmt::Display for ReconcileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Db(e) => write!(f, "database error: {}", e),
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Search(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReconcileError {}
+
+impl From<DbErr> for ReconcileError {
+    fn from(e: DbErr) -> Self {
+        Self::Db(e)
+    }
+}
+
+impl From<std::io::Error> for ReconcileError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<search::SearchError> for ReconcileError {
+    fn from(e: search::SearchError) -> Self {
+        Self::Search(e)
+    }
+}
+
+/// Inconsistencies found by one pass.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Artifacts without a version row.
+    pub orphan_files: Vec<PathBuf>,
+    /// Leftovers of uploads that failed or were interrupted.
+    pub stale_staging: Vec<PathBuf>,
+    /// Versions whose artifact is missing, as `(mod id, version id)`. These can only be
+    /// reported, the artifact is gone.
+    pub missing_artifacts: Vec<(Uuid, Uuid)>,
+    /// Search documents of mods that no longer exist.
+    pub stale_documents: Vec<Uuid>,
+    /// Mods without a search document.
+    pub unindexed_mods: Vec<Uuid>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.orphan_files.is_empty()
+            && self.stale_staging.is_empty()
+            && self.missing_artifacts.is_empty()
+            && self.stale_documents.is_empty()
+            && self.unindexed_mods.is_empty()
+    }
+
+    fn log(&self, repaired: bool) {
+        if self.is_clean() {
+            log::info!("reconcile: no inconsistencies found");
+            return;
+        }
+
+        let action = if repaired { "removed" } else { "found" };
+        for path in &self.orphan_files {
+            log::warn!("reconcile: {} orphaned artifact {}", action, path.display());
+        }
+        for path in &self.stale_staging {
+            log::warn!("reconcile: {} stale staged upload {}", action, path.display());
+        }
+        for (mod_id, version_id) in &self.missing_artifacts {
+            log::error!(
+                "reconcile: version {} of mod {} has no artifact",
+                version_id,
+                mod_id
+            );
+        }
+        for id in &self.stale_documents {
+            log::warn!("reconcile: {} search document of deleted mod {}", action, id);
+        }
+        for id in &self.unindexed_mods {
+            let action = if repaired { "indexed" } else { "found" };
+            log::warn!("reconcile: {} mod {} missing from search", action, id);
+        }
+    }
+}
+
+/// Seconds between passes, configured with `RECONCILE_INTERVAL`. `0` turns the reconciler off.
+pub fn interval() -> Option<Duration> {
+    let secs = std::env::var("RECONCILE_INTERVAL")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(60 * 60);
+
+    (secs > 0).then_some(Duration::from_secs(secs))
+}
+
+/// Whether passes fix what they find, configured with `RECONCILE_REPAIR`. Without it they
+/// only report.
+pub fn repair_enabled() -> bool {
+    matches!(
+        std::env::var("RECONCILE_REPAIR").as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
+
+/// Runs a pass every [`interval`] for as long as the server runs.
+pub async fn run(db: DatabaseConnection) {
+    let interval = match interval() {
+        Some(interval) => interval,
+        None => return,
+    };
+    let repair = repair_enabled();
+
+    loop {
+        actix_web::rt::time::sleep(interval).await;
+
+        match reconcile(&db, repair).await {
+            Ok(report) => report.log(repair),
+            Err(e) => log::error!("reconcile failed: {}", e),
+        }
+    }
+}
+
+/// Compares stored artifacts and search documents with the database, fixing what can be
+/// fixed when `repair` is set.
+pub async fn reconcile(db: &DatabaseConnection, repair: bool) -> Result<Report, ReconcileError> {
+    let mut report = Report::default();
+
+    let versions = Versions::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|v| (v.mod_id, v.id))
+        .collect::<HashSet<_>>();
+
+    let (orphan_files, stale_staging, missing_artifacts) = web::block({
+        let versions = versions.clone();
+        move || scan_files(&versions)
+    })
+    .await
+    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))??;
+
+    report.orphan_files = orphan_files;
+    report.stale_staging = stale_staging;
+    report.missing_artifacts = missing_artifacts;
+
+    let mod_ids = Mods::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|m| m.id)
+        .collect::<HashSet<_>>();
+    let indexed = search::indexed_mod_ids()
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    report.stale_documents = indexed.difference(&mod_ids).copied().collect();
+    report.unindexed_mods = mod_ids.difference(&indexed).copied().collect();
+
+    if repair {
+        for path in report.orphan_files.iter().chain(&report.stale_staging) {
+            std::fs::remove_file(path)?;
+        }
+        if !report.stale_documents.is_empty() {
+            search::remove_mods(&report.stale_documents).await?;
+        }
+        for id in &report.unindexed_mods {
+            search::index_mod(db, *id).await?;
+        }
+    }
+
+    Ok(report)
+}
+
+type FileScan = (Vec<PathBuf>, Vec<PathBuf>, Vec<(Uuid, Uuid)>);
+
+fn scan_files(versions: &HashSet<(Uuid, Uuid)>) -> std::io::Result<FileScan> {
+    let mut orphan_files = vec![];
+    let mut found = HashSet::new();
+
+    for dir in std::fs::read_dir("./data/cdn")? {
+        let dir = dir?;
+        // only `{mod id}` directories hold artifacts, user media lives next to them
+        let mod_id = match Uuid::parse_str(&dir.file_name().to_string_lossy()) {
+            Ok(mod_id) if dir.file_type()?.is_dir() => mod_id,
+            _ => continue,
+        };
+
+        for file in std::fs::read_dir(dir.path())? {
+            let file = file?;
+            let path = file.path();
+            if path.extension().map_or(true, |e| e != "forgemod") {
+                continue;
+            }
+
+            let version_id = path
+                .file_stem()
+                .and_then(|s| Uuid::parse_str(&s.to_string_lossy()).ok());
+            match version_id {
+                Some(version_id) if versions.contains(&(mod_id, version_id)) => {
+                    found.insert((mod_id, version_id));
+                }
+                // uploads publish their artifact just before committing, so a young file
+                // may belong to a version that wasn't loaded yet
+                _ if age(&file)? > GRACE_PERIOD => orphan_files.push(path),
+                _ => {}
+            }
+        }
+    }
+
+    let mut stale_staging = vec![];
+    if cdn::staging_dir().exists() {
+        for file in std::fs::read_dir(cdn::staging_dir())? {
+            let file = file?;
+            if age(&file)? > GRACE_PERIOD {
+                stale_staging.push(file.path());
+            }
+        }
+    }
+
+    let missing_artifacts = versions.difference(&found).copied().collect();
+
+    Ok((orphan_files, stale_staging, missing_artifacts))
+}
+
+fn age(file: &std::fs::DirEntry) -> std::io::Result<Duration> {
+    Ok(file.metadata()?.modified()?.elapsed().unwrap_or_default())
+}
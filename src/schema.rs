@@ -1,8 +1,5 @@
-use entity::prelude::*;
-use juniper::{
-    graphql_value, EmptyMutation, EmptySubscription, FieldResult, GraphQLEnum, GraphQLObject,
-    RootNode,
-};
+use chrono::NaiveDate;
+use juniper::{graphql_value, EmptySubscription, FieldResult, GraphQLEnum, RootNode};
 
 #[derive(GraphQLEnum)]
 enum Episode {
@@ -11,13 +8,22 @@ enum Episode {
     Jedi,
 }
 
-use sea_orm::EntityTrait;
 use uuid::Uuid;
 
-use crate::auth::Authorization;
-use crate::mods::Mod;
-use crate::users::User;
-use crate::{mods, users, Database};
+use crate::auth::{authorize, Authorization, Permission};
+use crate::categories::GCategory;
+use crate::compatibility::GCompatibilityReport;
+use crate::gallery::GMedia;
+use crate::game_versions::GBeatSaberVersion;
+use crate::jobs::{GJob, JobStatus};
+use crate::mods::{Mod, ModFilter, ModSort, SortOrder};
+use crate::ratings::GRating;
+use crate::users::{ProfileUpdate, User, UserUpdate};
+use crate::versions::{GChangelog, GVersion};
+use crate::{
+    categories, compatibility, gallery, game_versions, jobs, mods, ratings, trending, users,
+    versions, Database,
+};
 
 pub struct QueryRoot;
 
@@ -58,6 +64,9 @@ impl QueryRoot {
         limit: Option<i32>,
         offset: Option<i32>,
         version: Option<String>,
+        filter: Option<ModFilter>,
+        sort: Option<ModSort>,
+        order: Option<SortOrder>,
     ) -> FieldResult<Vec<Mod>> {
         if limit > Some(10) {
             return Err(juniper::FieldError::new(
@@ -67,7 +76,16 @@ impl QueryRoot {
         }
         let db = db.pool.clone();
 
-        mods::find_all(&db, limit.unwrap_or(10), offset.unwrap_or(0), version).await
+        mods::find_all(
+            &db,
+            limit.unwrap_or(10),
+            offset.unwrap_or(0),
+            version,
+            filter.unwrap_or_default(),
+            sort,
+            order,
+        )
+        .await
     }
 
     #[cached::cached(time=120)]
@@ -91,46 +109,493 @@ impl QueryRoot {
         mods::find_by_author(&db, id).await
     }
 
-    #[cached::cached(time=120)]
-    async fn categories(db: &Database) -> FieldResult<Vec<GCategory>> {
+    async fn categories(
+        db: &Database,
+        include_retired: Option<bool>,
+    ) -> FieldResult<Vec<GCategory>> {
         let db = db.pool.clone();
 
-        Ok(Categories::find()
-            .all(&db)
-            .await
-            .unwrap()
-            .iter()
-            .map(|c| GCategory {
-                name: c.name.clone(),
-                description: c.description.clone(),
-            })
-            .collect::<Vec<_>>())
+        categories::find_all(&db, include_retired.unwrap_or(false)).await
     }
 
-    #[cached::cached(time=120)]
-    async fn beat_saber_versions(db: &Database) -> FieldResult<Vec<String>> {
+    async fn beat_saber_versions(
+        db: &Database,
+        include_deprecated: Option<bool>,
+    ) -> FieldResult<Vec<GBeatSaberVersion>> {
         let db = db.pool.clone();
 
-        Ok(BeatSaberVersions::find()
-            .all(&db)
-            .await
-            .unwrap()
-            .iter()
-            .map(|v| v.ver.clone())
-            .collect::<Vec<_>>())
+        game_versions::find_all(&db, include_deprecated.unwrap_or(false)).await
+    }
+
+    async fn compatibility_reports(
+        db: &Database,
+        slug: String,
+        version: String,
+        game_version: Option<String>,
+    ) -> FieldResult<Vec<GCompatibilityReport>> {
+        let db = db.pool.clone();
+
+        compatibility::find_reports(&db, slug, version, game_version).await
+    }
+
+    /// Release notes of the approved versions of a mod after `from` up to and including `to`.
+    async fn changelog(
+        db: &Database,
+        slug: String,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> FieldResult<GChangelog> {
+        let db = db.pool.clone();
+
+        versions::changelog(&db, slug, from, to).await
+    }
+
+    async fn mod_reviews(
+        db: &Database,
+        slug: String,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> FieldResult<Vec<GRating>> {
+        if limit > Some(50) {
+            return Err(juniper::FieldError::new(
+                "Limit must be less than 50",
+                graphql_value!({ "limit": "Limit must be less than 50" }),
+            ));
+        }
+        let db = db.pool.clone();
+
+        ratings::find_reviews(&db, slug, limit.unwrap_or(20), offset.unwrap_or(0)).await
+    }
+
+    /// Mods gaining downloads fastest lately, weighted by their rating. `game_version` is a
+    /// version or alias.
+    async fn trending_mods(
+        db: &Database,
+        category: Option<String>,
+        game_version: Option<String>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> FieldResult<Vec<Mod>> {
+        if limit > Some(10) {
+            return Err(juniper::FieldError::new(
+                "Limit must be less than 10",
+                graphql_value!({ "limit": "Limit must be less than 10" }),
+            ));
+        }
+        let db = db.pool.clone();
+
+        trending::find(
+            &db,
+            category,
+            game_version,
+            limit.unwrap_or(10),
+            offset.unwrap_or(0),
+        )
+        .await
+    }
+
+    async fn jobs(
+        db: &Database,
+        auth: String,
+        status: Option<JobStatus>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> FieldResult<Vec<GJob>> {
+        if limit > Some(100) {
+            return Err(juniper::FieldError::new(
+                "Limit must be less than 100",
+                graphql_value!({ "limit": "Limit must be less than 100" }),
+            ));
+        }
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        jobs::find_all(&db, status, limit.unwrap_or(50), offset.unwrap_or(0)).await
     }
 }
 
-#[derive(GraphQLObject)]
-pub struct GCategory {
-    name: String,
-    description: String,
+pub struct MutationRoot;
+
+#[juniper::graphql_object(context = Database)]
+impl MutationRoot {
+    async fn add_beat_saber_version(
+        db: &Database,
+        auth: String,
+        ver: String,
+        release_date: Option<NaiveDate>,
+        is_latest: Option<bool>,
+    ) -> FieldResult<GBeatSaberVersion> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        game_versions::create(&db, ver, release_date, is_latest.unwrap_or(false)).await
+    }
+
+    async fn deprecate_beat_saber_version(
+        db: &Database,
+        auth: String,
+        ver: String,
+        deprecated: Option<bool>,
+    ) -> FieldResult<GBeatSaberVersion> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        game_versions::set_deprecated(&db, ver, deprecated.unwrap_or(true)).await
+    }
+
+    async fn set_latest_beat_saber_version(
+        db: &Database,
+        auth: String,
+        ver: String,
+    ) -> FieldResult<GBeatSaberVersion> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        game_versions::set_latest(&db, ver).await
+    }
+
+    async fn set_beat_saber_version_release_date(
+        db: &Database,
+        auth: String,
+        ver: String,
+        release_date: Option<NaiveDate>,
+    ) -> FieldResult<GBeatSaberVersion> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        game_versions::set_release_date(&db, ver, release_date).await
+    }
+
+    async fn alias_beat_saber_version(
+        db: &Database,
+        auth: String,
+        ver: String,
+        alias: String,
+    ) -> FieldResult<GBeatSaberVersion> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        game_versions::add_alias(&db, ver, alias).await
+    }
+
+    async fn remove_beat_saber_version_alias(
+        db: &Database,
+        auth: String,
+        alias: String,
+    ) -> FieldResult<GBeatSaberVersion> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        game_versions::remove_alias(&db, alias).await
+    }
+
+    async fn create_category(
+        db: &Database,
+        auth: String,
+        name: String,
+        description: String,
+    ) -> FieldResult<GCategory> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        categories::create(&db, name, description).await
+    }
+
+    async fn update_category(
+        db: &Database,
+        auth: String,
+        name: String,
+        new_name: Option<String>,
+        description: Option<String>,
+    ) -> FieldResult<GCategory> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        categories::update(&db, name, new_name, description).await
+    }
+
+    async fn retire_category(
+        db: &Database,
+        auth: String,
+        name: String,
+        retired: Option<bool>,
+    ) -> FieldResult<GCategory> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        categories::set_retired(&db, name, retired.unwrap_or(true)).await
+    }
+
+    async fn update_me(db: &Database, auth: String, input: ProfileUpdate) -> FieldResult<User> {
+        let db = db.pool.clone();
+        let user = authorize(&db, Some(auth), Permission::EDIT_SELF).await?;
+
+        users::update_me(&db, user, input).await
+    }
+
+    async fn update_user(
+        db: &Database,
+        auth: String,
+        id: Uuid,
+        input: UserUpdate,
+    ) -> FieldResult<User> {
+        let db = db.pool.clone();
+        let admin = authorize(&db, Some(auth), Permission::EDIT_OTHER_USERS).await?;
+
+        users::update_user(&db, &admin, id, input).await
+    }
+
+    async fn set_mod_categories(
+        db: &Database,
+        auth: String,
+        slug: String,
+        categories: Vec<String>,
+        tags: Vec<String>,
+    ) -> FieldResult<Mod> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        mods::set_categories(&db, &user, slug, categories, tags).await
+    }
+
+    /// Replaces the Markdown long description of a mod. Leaving out `description` removes it.
+    async fn set_mod_long_description(
+        db: &Database,
+        auth: String,
+        slug: String,
+        description: Option<String>,
+    ) -> FieldResult<Mod> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        mods::set_long_description(&db, &user, slug, description).await
+    }
+
+    /// Adds a video link to the end of a mod's gallery. Screenshots are uploaded to
+    /// `/mods/{slug}/gallery`.
+    async fn add_mod_gallery_video(
+        db: &Database,
+        auth: String,
+        slug: String,
+        url: String,
+        caption: Option<String>,
+    ) -> FieldResult<GMedia> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        gallery::add_video(&db, &user, slug, url, caption).await
+    }
+
+    async fn set_mod_gallery_caption(
+        db: &Database,
+        auth: String,
+        slug: String,
+        id: Uuid,
+        caption: Option<String>,
+    ) -> FieldResult<GMedia> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        gallery::set_caption(&db, &user, slug, id, caption).await
+    }
+
+    /// Orders a mod's gallery like `ids`, which has to list every item once.
+    async fn reorder_mod_gallery(
+        db: &Database,
+        auth: String,
+        slug: String,
+        ids: Vec<Uuid>,
+    ) -> FieldResult<Vec<GMedia>> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        gallery::reorder(&db, &user, slug, ids).await
+    }
+
+    async fn remove_mod_gallery_item(
+        db: &Database,
+        auth: String,
+        slug: String,
+        id: Uuid,
+    ) -> FieldResult<Vec<GMedia>> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        gallery::remove(&db, &user, slug, id).await
+    }
+
+    /// Adds and removes game versions an uploaded version supports. Game versions may be given
+    /// by version or alias.
+    async fn set_version_game_versions(
+        db: &Database,
+        auth: String,
+        slug: String,
+        version: String,
+        add: Option<Vec<String>>,
+        remove: Option<Vec<String>>,
+    ) -> FieldResult<GVersion> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        versions::set_game_versions(
+            &db,
+            &user,
+            slug,
+            version,
+            add.unwrap_or_default(),
+            remove.unwrap_or_default(),
+        )
+        .await
+    }
+
+    /// Replaces the Markdown release notes of an uploaded version. Leaving out `notes` removes
+    /// them.
+    async fn set_release_notes(
+        db: &Database,
+        auth: String,
+        slug: String,
+        version: String,
+        notes: Option<String>,
+    ) -> FieldResult<GVersion> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        versions::set_release_notes(&db, &user, slug, version, notes).await
+    }
+
+    /// Reports whether a version works on a game version.
+    async fn submit_compatibility_report(
+        db: &Database,
+        auth: String,
+        slug: String,
+        version: String,
+        game_version: String,
+        works: bool,
+        notes: Option<String>,
+    ) -> FieldResult<GCompatibilityReport> {
+        let db = db.pool.clone();
+        let user = authorize(&db, Some(auth), Permission::VIEW_SELF).await?;
+
+        compatibility::submit(&db, &user, slug, version, game_version, works, notes).await
+    }
+
+    /// Makes a game version that players report as working an officially supported one.
+    async fn promote_compatibility_report(
+        db: &Database,
+        auth: String,
+        slug: String,
+        version: String,
+        game_version: String,
+    ) -> FieldResult<GVersion> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::APPROVE_MOD).await?;
+
+        compatibility::promote(&db, slug, version, game_version).await
+    }
+
+    /// Rates a mod from 1 to 5. Rating it again replaces the earlier rating.
+    async fn rate_mod(
+        db: &Database,
+        auth: String,
+        slug: String,
+        rating: i32,
+        review: Option<String>,
+        version: Option<String>,
+    ) -> FieldResult<GRating> {
+        let db = db.pool.clone();
+        let user = authorize(&db, Some(auth), Permission::VIEW_SELF).await?;
+
+        ratings::rate(&db, &user, slug, rating, review, version).await
+    }
+
+    async fn remove_mod_rating(db: &Database, auth: String, slug: String) -> FieldResult<bool> {
+        let db = db.pool.clone();
+        let user = authorize(&db, Some(auth), Permission::VIEW_SELF).await?;
+
+        ratings::remove(&db, &user, slug).await
+    }
+
+    /// Answers a review of one of your mods. Leaving out `reply` removes the answer.
+    async fn reply_to_review(
+        db: &Database,
+        auth: String,
+        id: Uuid,
+        reply: Option<String>,
+    ) -> FieldResult<GRating> {
+        let db = db.pool.clone();
+        let user = authorize(
+            &db,
+            Some(auth),
+            Permission::EDIT_MOD | Permission::EDIT_OTHER_MODS,
+        )
+        .await?;
+
+        ratings::reply(&db, &user, id, reply).await
+    }
+
+    async fn set_review_hidden(
+        db: &Database,
+        auth: String,
+        id: Uuid,
+        hidden: bool,
+    ) -> FieldResult<GRating> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::APPROVE_MOD).await?;
+
+        ratings::set_hidden(&db, id, hidden).await
+    }
+
+    async fn retry_job(db: &Database, auth: String, id: Uuid) -> FieldResult<GJob> {
+        let db = db.pool.clone();
+        authorize(&db, Some(auth), Permission::EDIT_SITE_DATA).await?;
+
+        jobs::retry(&db, id).await
+    }
 }
 
-pub type Schema =
-    RootNode<'static, QueryRoot, EmptyMutation<Database>, EmptySubscription<Database>>;
+pub type Schema = RootNode<'static, QueryRoot, MutationRoot, EmptySubscription<Database>>;
 
 pub fn create_schema() -> Schema {
     // let sub = EmptySubscription::<Database>::new();
-    Schema::new(QueryRoot {}, EmptyMutation::new(), EmptySubscription::new())
+    Schema::new(QueryRoot {}, MutationRoot {}, EmptySubscription::new())
 }
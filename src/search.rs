@@ -0,0 +1,216 @@
+use std::fmt;
+
+use entity::prelude::*;
+use meilisearch_entity::prelude::*;
+use meilisearch_sdk::{
+    client::Client, documents::DocumentsQuery, indexes::Index, settings::Settings,
+};
+use sea_orm::{prelude::Uuid, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use semver::Version;
+use serde::Deserialize;
+
+#[derive(Debug)]
+pub enum SearchError {
+    Db(DbErr),
+    Meili(meilisearch_sdk::errors::Error),
+    NotFound,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Db(e) => write!(f, "database error: {}", e),
+            Self::Meili(e) => write!(f, "meilisearch error: {}", e),
+            Self::NotFound => write!(f, "mod not found"),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+impl From<DbErr> for SearchError {
+    fn from(e: DbErr) -> Self {
+        Self::Db(e)
+    }
+}
+
+impl From<meilisearch_sdk::errors::Error> for SearchError {
+    fn from(e: meilisearch_sdk::errors::Error) -> Self {
+        Self::Meili(e)
+    }
+}
+
+pub fn client() -> Client {
+    Client::new(
+        std::env::var("MEILI_URL").unwrap(),
+        Some(std::env::var("MEILI_KEY").unwrap()),
+    )
+}
+
+pub fn mods_index() -> Index {
+    client().index(format!(
+        "{}_mods",
+        std::env::var("MEILI_PREFIX").unwrap_or("".to_string())
+    ))
+}
+
+pub async fn apply_settings() -> Result<(), SearchError> {
+    let settings = Settings::new()
+        .with_filterable_attributes(&["category", "categories", "tags", "mod_type"])
+        .with_searchable_attributes(&["name", "description"])
+        .with_sortable_attributes(&[
+            "stats.downloads",
+            "stats.rating",
+            "stats.rating_count",
+            "stats.trending_score",
+            "created_at",
+            "updated_at",
+        ]);
+    mods_index().set_settings(&settings).await?;
+    Ok(())
+}
+
+/// Builds the search document for a mod from the database.
+pub async fn build_mod(db: &DatabaseConnection, mod_id: Uuid) -> Result<MeiliMod, SearchError> {
+    let db_mod = Mods::find_by_id(mod_id)
+        .one(db)
+        .await?
+        .ok_or(SearchError::NotFound)?;
+
+    let category = Categories::find_by_id(db_mod.category)
+        .one(db)
+        .await?
+        .ok_or(SearchError::NotFound)?;
+
+    let categories = ModCategories::find()
+        .filter(entity::mod_categories::Column::ModId.eq(db_mod.id))
+        .find_also_related(Categories)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|(_, c)| c.map(|c| c.name))
+        .collect::<Vec<_>>();
+
+    let tags = ModTags::find()
+        .filter(entity::mod_tags::Column::ModId.eq(db_mod.id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| t.tag)
+        .collect::<Vec<_>>();
+
+    let author = Users::find_by_id(db_mod.author)
+        .one(db)
+        .await?
+        .ok_or(SearchError::NotFound)?;
+
+    let mod_stats = ModStats::find_by_id(db_mod.stats)
+        .one(db)
+        .await?
+        .ok_or(SearchError::NotFound)?;
+
+    let versions = ModVersions::find()
+        .filter(entity::mod_versions::Column::ModId.eq(db_mod.id))
+        .find_also_related(Versions)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|(_, v)| v.and_then(|v| Version::parse(&v.version).ok()))
+        .map(|version| MeiliVersion { version })
+        .collect::<Vec<_>>();
+
+    let supported_versions = ModBeatSaberVersions::find()
+        .filter(entity::mod_beat_saber_versions::Column::ModId.eq(db_mod.id))
+        .find_also_related(BeatSaberVersions)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|(_, v)| v.and_then(|v| Version::parse(&v.ver).ok()))
+        .collect::<Vec<_>>();
+
+    Ok(MeiliMod {
+        id: db_mod.id,
+        slug: db_mod.slug,
+        mod_type: db_mod.mod_type,
+        name: db_mod.name,
+        description: db_mod.description.unwrap_or("".to_string()),
+        category: category.name,
+        categories,
+        tags,
+        author: MeiliUser {
+            username: author.username.clone(),
+            display_name: author.display_name.unwrap_or(author.username),
+        },
+        stats: MeiliModStats {
+            downloads: mod_stats.downloads as u64,
+            rating: mod_stats.rating,
+            rating_count: mod_stats.rating_count as u64,
+            trending_score: mod_stats.trending_score,
+        },
+        versions,
+        supported_versions,
+        created_at: db_mod.created_at.and_utc().timestamp(),
+        updated_at: db_mod.updated_at.and_utc().timestamp(),
+    })
+}
+
+/// Adds or replaces the search document of a mod.
+pub async fn index_mod(db: &DatabaseConnection, mod_id: Uuid) -> Result<(), SearchError> {
+    let meilimod = build_mod(db, mod_id).await?;
+    mods_index().add_or_replace(&[meilimod], None).await?;
+    Ok(())
+}
+
+/// Reindexes every mod by `author`, logging failures instead of returning them.
+pub async fn reindex_author(db: &DatabaseConnection, author: Uuid) {
+    let mods = match Mods::find()
+        .filter(entity::mods::Column::Author.eq(author))
+        .all(db)
+        .await
+    {
+        Ok(mods) => mods,
+        Err(e) => {
+            log::error!("failed to load mods of {}: {}", author, e);
+            return;
+        }
+    };
+
+    for m in mods {
+        if let Err(e) = index_mod(db, m.id).await {
+            log::error!("failed to reindex mod {}: {}", m.id, e);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DocumentId {
+    id: Uuid,
+}
+
+/// Ids of every mod that has a search document.
+pub async fn indexed_mod_ids() -> Result<Vec<Uuid>, SearchError> {
+    const PAGE: usize = 1000;
+
+    let index = mods_index();
+    let mut ids = vec![];
+    loop {
+        let page = DocumentsQuery::new(&index)
+            .with_fields(["id"])
+            .with_offset(ids.len())
+            .with_limit(PAGE)
+            .execute::<DocumentId>()
+            .await?;
+
+        let done = page.results.len() < PAGE;
+        ids.extend(page.results.into_iter().map(|d| d.id));
+        if done {
+            return Ok(ids);
+        }
+    }
+}
+
+/// Removes the search documents of `ids`.
+pub async fn remove_mods(ids: &[Uuid]) -> Result<(), SearchError> {
+    mods_index().delete_documents(ids).await?;
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use juniper::FieldResult;
+use sea_orm::{
+    prelude::Uuid, ConnectionTrait, DatabaseConnection, DbErr, FromQueryResult, Statement,
+    TransactionTrait,
+};
+
+use crate::{
+    jobs,
+    mods::{self, Mod, ModFilter, ModSort, SortOrder},
+};
+
+/// Days of downloads that count towards the score.
+const WINDOW_DAYS: i32 = 14;
+/// Days after which a download counts half as much.
+const HALF_LIFE_DAYS: f64 = 3.0;
+/// Rating a mod is assumed to have before it has ratings of its own. The score is scaled by
+/// the rating relative to this, so an average mod keeps its download velocity.
+const PRIOR_RATING: f64 = 3.0;
+/// How many ratings the assumed rating is worth, so a single vote can't swing the score.
+const PRIOR_WEIGHT: f64 = 5.0;
+
+/// Seconds between refreshes, configured with `TRENDING_INTERVAL`. `0` turns refreshing off.
+pub fn interval() -> Option<Duration> {
+    let secs = std::env::var("TRENDING_INTERVAL")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(15 * 60);
+
+    (secs > 0).then_some(Duration::from_secs(secs))
+}
+
+#[derive(FromQueryResult)]
+struct Changed {
+    mod_id: Uuid,
+}
+
+/// Recomputes the trending score of every mod from its recent downloads and rating, and
+/// reindexes the mods whose score changed.
+pub async fn refresh(db: &DatabaseConnection) -> Result<usize, DbErr> {
+    let trans = db.begin().await?;
+
+    let changed = Changed::find_by_statement(Statement::from_sql_and_values(
+        trans.get_database_backend(),
+        r#"WITH recent AS (
+               SELECT versions.mod_id AS mod_id,
+                      SUM(
+                          version_downloads.downloads
+                          * power(0.5, (CURRENT_DATE - version_downloads.day)::float8 / $1)
+                      ) AS velocity
+               FROM version_downloads
+               JOIN versions ON versions.id = version_downloads.version_id
+               WHERE version_downloads.day > CURRENT_DATE - $2::int
+               GROUP BY versions.mod_id
+           ),
+           scores AS (
+               SELECT mods.id AS mod_id,
+                      mods.stats AS stats_id,
+                      ROUND((
+                          COALESCE(recent.velocity, 0)
+                          * (mod_stats.rating * mod_stats.rating_count + $3 * $4)
+                          / (mod_stats.rating_count + $4)
+                          / $3
+                      )::numeric, 3)::float8 AS score
+               FROM mods
+               JOIN mod_stats ON mod_stats.id = mods.stats
+               LEFT JOIN recent ON recent.mod_id = mods.id
+           )
+           UPDATE mod_stats SET trending_score = scores.score
+           FROM scores
+           WHERE mod_stats.id = scores.stats_id
+             AND mod_stats.trending_score <> scores.score
+           RETURNING scores.mod_id AS mod_id"#,
+        [
+            HALF_LIFE_DAYS.into(),
+            WINDOW_DAYS.into(),
+            PRIOR_RATING.into(),
+            PRIOR_WEIGHT.into(),
+        ],
+    ))
+    .all(&trans)
+    .await?;
+
+    for c in &changed {
+        jobs::enqueue(&trans, jobs::Job::IndexMod { mod_id: c.mod_id }).await?;
+    }
+
+    trans.commit().await?;
+
+    Ok(changed.len())
+}
+
+/// Refreshes the scores right away, then every [`interval`] for as long as the server runs.
+pub async fn run(db: DatabaseConnection) {
+    let interval = match interval() {
+        Some(interval) => interval,
+        None => return,
+    };
+
+    loop {
+        match refresh(&db).await {
+            Ok(changed) => log::info!("trending: refreshed {} scores", changed),
+            Err(e) => log::error!("trending refresh failed: {}", e),
+        }
+
+        actix_web::rt::time::sleep(interval).await;
+    }
+}
+
+/// Mods by trending score, optionally only those in `category` or supporting `game_version`.
+pub async fn find(
+    db: &DatabaseConnection,
+    category: Option<String>,
+    game_version: Option<String>,
+    limit: i32,
+    offset: i32,
+) -> FieldResult<Vec<Mod>> {
+    mods::find_all(
+        db,
+        limit,
+        offset,
+        game_version,
+        ModFilter {
+            category,
+            ..Default::default()
+        },
+        Some(ModSort::Trending),
+        Some(SortOrder::Desc),
+    )
+    .await
+}
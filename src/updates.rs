@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use actix_web::{post, web, HttpResponse};
+use entity::prelude::*;
+use sea_orm::{sea_query::Query, ColumnTrait, EntityTrait, QueryFilter};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{game_versions, validation, Database};
+
+/// Most mods one request may check.
+const MAX_MODS: usize = 500;
+
+#[derive(Deserialize)]
+pub struct UpdateCheckReq {
+    /// Game version the mods are installed on, by version or alias.
+    pub game_version: String,
+    pub mods: Vec<InstalledMod>,
+}
+
+#[derive(Deserialize)]
+pub struct InstalledMod {
+    pub slug: String,
+    pub installed_version: String,
+}
+
+#[derive(Serialize)]
+pub struct UpdateInfo {
+    pub slug: String,
+    pub installed_version: String,
+    /// Newest approved version supporting the game version, `None` if there is none or the
+    /// mod doesn't exist.
+    pub latest: Option<LatestVersion>,
+    pub update_available: bool,
+}
+
+#[derive(Serialize)]
+pub struct LatestVersion {
+    pub version: String,
+    pub download_url: String,
+    /// Hex encoded sha256 of the package.
+    pub hash: String,
+    /// Whether semver allows the update to break the installed version's api: a new major
+    /// version, or a new minor version before 1.0.0.
+    pub breaking: bool,
+}
+
+/// Checks a mod manager's installed mods for updates in one request.
+#[post("/mods/updates")]
+pub async fn check_updates(
+    db: web::Data<Database>,
+    req: web::Json<UpdateCheckReq>,
+) -> HttpResponse {
+    let req = req.into_inner();
+
+    if req.mods.len() > MAX_MODS {
+        return validation::problems_response(vec![format!(
+            "At most {} mods can be checked at once",
+            MAX_MODS
+        )]);
+    }
+
+    let mut problems = vec![];
+    let mut installed = vec![];
+    for m in &req.mods {
+        match Version::parse(&m.installed_version) {
+            Ok(v) => installed.push(v),
+            Err(_) => problems.push(format!(
+                "The installed version `{}` of `{}` is not valid semver",
+                m.installed_version, m.slug
+            )),
+        }
+    }
+    if !problems.is_empty() {
+        return validation::problems_response(problems);
+    }
+
+    let game_version = match game_versions::resolve(&db.pool, &req.game_version).await {
+        Ok(Some(game_version)) => game_version,
+        Ok(None) => {
+            return validation::problems_response(vec![format!(
+                "Unknown game version `{}`",
+                req.game_version
+            )])
+        }
+        Err(e) => {
+            log::error!("failed to resolve game version: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let mods = match Mods::find()
+        .filter(entity::mods::Column::Slug.is_in(req.mods.iter().map(|m| m.slug.clone())))
+        .all(&db.pool)
+        .await
+    {
+        Ok(mods) => mods,
+        Err(e) => {
+            log::error!("failed to load mods: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    // every approved version of the requested mods that supports the game version
+    let versions = match Versions::find()
+        .filter(entity::versions::Column::ModId.is_in(mods.iter().map(|m| m.id)))
+        .filter(entity::versions::Column::Approved.eq(true))
+        .filter(
+            entity::versions::Column::Id.in_subquery(
+                Query::select()
+                    .column(entity::version_beat_saber_versions::Column::VersionId)
+                    .from(VersionBeatSaberVersions)
+                    .and_where(
+                        entity::version_beat_saber_versions::Column::BeatSaberVersionId
+                            .eq(game_version.id),
+                    )
+                    .to_owned(),
+            ),
+        )
+        .all(&db.pool)
+        .await
+    {
+        Ok(versions) => versions,
+        Err(e) => {
+            log::error!("failed to load versions: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let mut newest: HashMap<_, (Version, entity::versions::Model)> = HashMap::new();
+    for v in versions {
+        let parsed = match Version::parse(&v.version) {
+            Ok(parsed) => parsed,
+            Err(_) => continue,
+        };
+        match newest.get(&v.mod_id) {
+            Some((current, _)) if *current >= parsed => {}
+            _ => {
+                newest.insert(v.mod_id, (parsed, v));
+            }
+        }
+    }
+
+    let slugs = mods
+        .iter()
+        .map(|m| (m.slug.as_str(), m.id))
+        .collect::<HashMap<_, _>>();
+
+    let updates = req
+        .mods
+        .iter()
+        .zip(installed)
+        .map(|(m, installed)| {
+            let latest = slugs
+                .get(m.slug.as_str())
+                .and_then(|id| newest.get(id))
+                .map(|(version, v)| {
+                    // `^installed` is exactly the range semver considers compatible
+                    let compatible = VersionReq::parse(&format!("^{}", installed))
+                        .is_ok_and(|req| req.matches(version));
+                    (version, v, compatible)
+                });
+
+            UpdateInfo {
+                slug: m.slug.clone(),
+                installed_version: m.installed_version.clone(),
+                update_available: latest
+                    .as_ref()
+                    .is_some_and(|(version, _, _)| **version > installed),
+                latest: latest.map(|(version, v, compatible)| LatestVersion {
+                    version: version.to_string(),
+                    download_url: v.download_url.clone(),
+                    hash: v.artifact_hash.clone(),
+                    breaking: *version > installed && !compatible,
+                }),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    HttpResponse::Ok().json(json!({ "updates": updates }))
+}
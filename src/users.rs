@@ -2,23 +2,27 @@ use actix_web::{post, web, HttpRequest, HttpResponse, Responder, get};
 use chrono::{DateTime, Utc};
 use entity::prelude::*;
 use juniper::{
-    graphql_value, FieldError, FieldResult, GraphQLObject,
+    graphql_value, FieldError, FieldResult, GraphQLInputObject, GraphQLObject,
+};
+use sea_orm::{
+    ActiveModelTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QuerySelect, Set,
 };
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QuerySelect, Set, DatabaseConnection};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
 
 use crate::{
-    auth::{validate_permissions, Authorization, JWTAuth, Permission},
+    auth::{get_user_from_request, validate_permissions, Authorization, Permission},
+    cdn,
+    images::{self, ImageKind, UploadError},
     mods::{self, Mod},
-    Database, KEY,
+    search, Database,
 };
 
 #[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
 pub struct User {
     pub id: Uuid,
-    pub github_id: String,
+    pub github_id: Option<String>,
     pub username: String,
     pub display_name: Option<String>,
 
@@ -30,6 +34,8 @@ pub struct User {
     pub permissions: i32,
     pub avatar: Option<String>,
     pub banner: Option<String>,
+    /// Upload size limit in bytes, when it differs from the global one.
+    pub upload_limit: Option<i32>,
 
     // Authed field
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -42,7 +48,7 @@ impl User {
     async fn from_db_user(db: &DatabaseConnection, u: entity::users::Model) -> Result<Self, FieldError> {
         Ok(User {
             id: Uuid::from_bytes(*u.id.as_bytes()),
-            github_id: u.github_id.to_string(),
+            github_id: u.github_id.map(|id| id.to_string()),
             username: u.username,
             display_name: u.display_name,
             email: Some(u.email),
@@ -52,6 +58,7 @@ impl User {
                 .unwrap(),
             avatar: u.avatar,
             banner: u.banner,
+            upload_limit: u.upload_limit,
             permissions: u.permissions,
             api_key: Some(u.api_key.to_string()),
             created_at: u.created_at.and_utc(),
@@ -146,83 +153,245 @@ pub async fn find_by_id(db: &DatabaseConnection, _id: Uuid, auth: Authorization)
     Ok(user)
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct UserAuthReq {
-    pub code: String,
+bitflags::bitflags! {
+    /// GitHub-derived profile fields the user has set themselves, which logins leave alone.
+    pub struct ProfileOverrides: i32 {
+        const BIO = 1 << 0;
+        const AVATAR = 1 << 1;
+    }
 }
 
-#[post("/auth/github")]
-pub async fn user_auth(
-    _req: HttpRequest,
-    data: web::Data<Database>,
-    info: web::Query<UserAuthReq>,
-) -> impl Responder {
-    let code = &info.code;
-
-    let gat = minreq::post("https://github.com/login/oauth/access_token")
-        .with_header("User-Agent", "forge-registry")
-        .with_json(&json!({
-            "client_id": std::env::var("GITHUB_CLIENT_ID").unwrap(),
-            "client_secret": std::env::var("GITHUB_CLIENT_SECRET").unwrap(),
-            "code": code,
-        }))
-        .unwrap()
-        .send()
-        .unwrap();
+const MAX_DISPLAY_NAME_LEN: usize = 32;
+const MAX_BIO_LEN: usize = 512;
 
-    let gat = gat.as_str().unwrap().split('&').collect::<Vec<_>>()[0]
-        .split('=')
-        .collect::<Vec<_>>()[1]
-        .to_string();
+/// Profile fields a user may change on their own account. An empty string clears a field.
+#[derive(GraphQLInputObject, Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+}
 
-    let github_user = minreq::get("https://api.github.com/user")
-        .with_header("User-Agent", "forge-registry")
-        .with_header("Authorization", format!("Bearer {}", gat))
-        .send()
-        .unwrap();
+/// Fields an admin may change on any account. An empty string clears a field.
+#[derive(GraphQLInputObject, Debug, Clone, Default)]
+pub struct UserUpdate {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    /// Needs `EDIT_SITE_DATA`, and only permissions the admin holds can be granted or revoked.
+    pub permissions: Option<i32>,
+    pub clear_avatar: Option<bool>,
+    pub clear_banner: Option<bool>,
+    /// Upload size limit in bytes, capped by the global limit. `0` goes back to the global limit.
+    pub upload_limit: Option<i32>,
+}
 
-    log::debug!("{}", github_user.as_str().unwrap());
-    let github_user = serde_json::from_str::<GithubUser>(github_user.as_str().unwrap()).unwrap();
+fn invalid(field: &str, msg: &str) -> FieldError {
+    FieldError::new(msg, graphql_value!({ "field": (field.to_string()) }))
+}
 
-    let mby_user = Users::find()
-        .filter(entity::users::Column::GithubId.eq(github_user.id as i32))
-        .one(&data.pool)
-        .await
-        .unwrap();
+fn validate_profile(display_name: &Option<String>, bio: &Option<String>) -> FieldResult<()> {
+    if let Some(name) = display_name {
+        if name.trim().chars().count() > MAX_DISPLAY_NAME_LEN {
+            return Err(invalid(
+                "displayName",
+                &format!("Display name must be at most {} characters", MAX_DISPLAY_NAME_LEN),
+            ));
+        }
+        if name.chars().any(|c| c.is_control()) {
+            return Err(invalid(
+                "displayName",
+                "Display name can not contain control characters",
+            ));
+        }
+    }
 
-    if mby_user.is_none() {
-        let usr = entity::users::ActiveModel {
-            github_id: Set(github_user.id as i32),
-            username: Set(github_user.login),
-            email: Set(github_user.email),
-            bio: Set(github_user.bio),
-            avatar: Set(github_user.avatar_url),
-            permissions: Set(7),
+    if let Some(bio) = bio {
+        if bio.trim().chars().count() > MAX_BIO_LEN {
+            return Err(invalid(
+                "bio",
+                &format!("Bio must be at most {} characters", MAX_BIO_LEN),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.trim().to_string();
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+async fn apply_update(
+    db: &DatabaseConnection,
+    user: entity::users::Model,
+    update: UserUpdate,
+) -> FieldResult<User> {
+    validate_profile(&update.display_name, &update.bio)?;
+
+    let old_display_name = user.display_name.clone();
+    let mut overrides = ProfileOverrides::from_bits_truncate(user.profile_overrides);
+    let mut am = user.into_active_model();
+
+    if let Some(display_name) = update.display_name {
+        am.display_name = Set(non_empty(display_name));
+    }
+    if let Some(bio) = update.bio {
+        // clearing the bio hands it back to GitHub on the next login
+        let bio = non_empty(bio);
+        overrides.set(ProfileOverrides::BIO, bio.is_some());
+        am.bio = Set(bio);
+    }
+    if let Some(permissions) = update.permissions {
+        am.permissions = Set(permissions);
+    }
+    if update.clear_avatar.unwrap_or(false) {
+        overrides.remove(ProfileOverrides::AVATAR);
+        am.avatar = Set(None);
+    }
+    if update.clear_banner.unwrap_or(false) {
+        am.banner = Set(None);
+    }
+    if let Some(upload_limit) = update.upload_limit {
+        if upload_limit < 0 {
+            return Err(invalid("uploadLimit", "Upload limit can not be negative"));
+        }
+        am.upload_limit = Set(Some(upload_limit).filter(|l| *l > 0));
+    }
+    am.profile_overrides = Set(overrides.bits());
+    am.updated_at = Set(Utc::now().naive_utc());
+
+    let user = am.update(db).await?;
+
+    // search documents show the author's display name
+    if user.display_name != old_display_name {
+        search::reindex_author(db, user.id).await;
+    }
+
+    User::from_db_user(db, user).await
+}
+
+pub async fn update_me(
+    db: &DatabaseConnection,
+    user: entity::users::Model,
+    update: ProfileUpdate,
+) -> FieldResult<User> {
+    apply_update(
+        db,
+        user,
+        UserUpdate {
+            display_name: update.display_name,
+            bio: update.bio,
             ..Default::default()
-        };
+        },
+    )
+    .await
+}
 
-        Users::insert(usr).exec(&data.pool).await.unwrap();
+pub async fn update_user(
+    db: &DatabaseConnection,
+    admin: &entity::users::Model,
+    id: Uuid,
+    update: UserUpdate,
+) -> FieldResult<User> {
+    let id = sea_orm::prelude::Uuid::from_bytes(*id.as_bytes());
+    let user = Users::find_by_id(id).one(db).await?.ok_or_else(|| {
+        FieldError::new(
+            "User not found",
+            graphql_value!({ "notFound": "User not found" }),
+        )
+    })?;
+
+    if let Some(permissions) = update.permissions {
+        let permissions = Permission::from_bits(permissions)
+            .ok_or_else(|| invalid("permissions", "Unknown permissions"))?;
+        let changed = permissions ^ Permission::from_bits_retain(user.permissions);
+        let held = Permission::from_bits_retain(admin.permissions);
+
+        if !validate_permissions(admin, Permission::EDIT_SITE_DATA).await
+            || !held.contains(changed)
+        {
+            return Err(FieldError::new(
+                "Unauthorized",
+                graphql_value!({ "auth": "Unauthorized" }),
+            ));
+        }
     }
 
-    let user = Users::find()
-        .filter(entity::users::Column::GithubId.eq(github_user.id as i32))
-        .one(&data.pool)
-        .await
-        .unwrap()
-        .unwrap();
+    apply_update(db, user, update).await
+}
 
-    let jwt = JWTAuth::new(user).encode(*KEY.clone());
+#[post("/me/avatar")]
+pub async fn upload_avatar(
+    req: HttpRequest,
+    data: web::Data<Database>,
+    payload: web::Payload,
+) -> impl Responder {
+    upload_user_image(req, data, payload, ImageKind::Avatar).await
+}
 
-    HttpResponse::Ok().json(json!({ "jwt": jwt }))
+#[post("/me/banner")]
+pub async fn upload_banner(
+    req: HttpRequest,
+    data: web::Data<Database>,
+    payload: web::Payload,
+) -> impl Responder {
+    upload_user_image(req, data, payload, ImageKind::Banner).await
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct GithubUser {
-    pub avatar_url: Option<String>,
-    pub bio: Option<String>,
-    pub email: String,
-    pub id: i64,
-    pub login: String,
+async fn upload_user_image(
+    req: HttpRequest,
+    data: web::Data<Database>,
+    payload: web::Payload,
+    kind: ImageKind,
+) -> HttpResponse {
+    let user = match get_user_from_request(&req, &data.pool).await {
+        Some(user) if validate_permissions(&user, Permission::EDIT_SELF).await => user,
+        _ => return HttpResponse::Unauthorized().body("Unauthorized"),
+    };
+
+    let buf = match cdn::read_limited(payload, images::MAX_IMAGE_SIZE).await {
+        Ok(buf) => buf,
+        Err(res) => return res,
+    };
+
+    let img = match cdn::store_upload(user.id, kind, buf).await {
+        Ok(img) => img,
+        Err(UploadError::Image(e)) => return HttpResponse::BadRequest().body(e.to_string()),
+        Err(e) => {
+            log::error!("failed to store {}: {}", kind.name(), e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let user_id = user.id;
+    let overrides = ProfileOverrides::from_bits_truncate(user.profile_overrides);
+    let mut am = user.into_active_model();
+    match kind {
+        ImageKind::Banner => am.banner = Set(Some(img.url.clone())),
+        _ => {
+            am.avatar = Set(Some(img.url.clone()));
+            am.profile_overrides = Set((overrides | ProfileOverrides::AVATAR).bits());
+        }
+    }
+    am.updated_at = Set(Utc::now().naive_utc());
+    if let Err(e) = am.update(&data.pool).await {
+        log::error!("failed to set {} of {}: {}", kind.name(), user_id, e);
+        // the user still points at the old image, so that one stays
+        if let Err(e) = cdn::remove_image(user_id, &img.stem) {
+            log::warn!("failed to remove unused {} of {}: {}", kind.name(), user_id, e);
+        }
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    if let Err(e) = cdn::prune_images(user_id, kind, &img.stem) {
+        log::warn!("failed to remove replaced {} of {}: {}", kind.name(), user_id, e);
+    }
+
+    HttpResponse::Ok().json(json!({ "url": img.url, "thumbnail": img.thumbnail }))
 }
 
 #[get("/me")]
@@ -0,0 +1,211 @@
+use actix_web::HttpResponse;
+use serde_json::json;
+
+/// Largest artifact (the mod's dll) a forgemod may carry.
+pub const MAX_ARTIFACT_SIZE: usize = 32 * 1024 * 1024;
+
+const MAX_SLUG_LEN: usize = 64;
+const MAX_NAME_LEN: usize = 64;
+const MAX_DESCRIPTION_LEN: usize = 1024;
+pub const MAX_RELEASE_NOTES_LEN: usize = 20_000;
+pub const MAX_LONG_DESCRIPTION_LEN: usize = 50_000;
+
+/// The parts of an uploaded forgemod that are stored as they are.
+pub struct ModManifest<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+    pub description: &'a str,
+    pub website: &'a str,
+    /// `None` for packages that carry no artifact.
+    pub artifact_size: Option<usize>,
+    pub release_notes: Option<&'a str>,
+    /// The README the package ships, if any.
+    pub long_description: Option<&'a str>,
+}
+
+/// Checks a manifest, returning every problem found rather than stopping at the first one.
+pub fn validate_manifest(m: &ModManifest) -> Vec<String> {
+    let mut problems = vec![];
+
+    if m.slug.is_empty() {
+        problems.push("The mod id is missing".to_string());
+    } else if m.slug.len() > MAX_SLUG_LEN
+        || !m
+            .slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        || !m.slug.starts_with(|c: char| c.is_ascii_alphanumeric())
+    {
+        problems.push(format!(
+            "The mod id `{}` must be at most {} lowercase letters, digits, dashes or underscores, starting with a letter or digit",
+            m.slug, MAX_SLUG_LEN
+        ));
+    }
+
+    if m.name.trim().is_empty() {
+        problems.push("The mod name is missing".to_string());
+    } else if m.name.chars().count() > MAX_NAME_LEN {
+        problems.push(format!(
+            "The mod name must be at most {} characters",
+            MAX_NAME_LEN
+        ));
+    }
+
+    if m.description.trim().is_empty() {
+        problems.push("The mod description is missing".to_string());
+    } else if m.description.chars().count() > MAX_DESCRIPTION_LEN {
+        problems.push(format!(
+            "The mod description must be at most {} characters",
+            MAX_DESCRIPTION_LEN
+        ));
+    }
+
+    if !m.website.is_empty() {
+        match url::Url::parse(m.website) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+            _ => problems.push(format!(
+                "The website `{}` is not a valid http(s) URL",
+                m.website
+            )),
+        }
+    }
+
+    if m.artifact_size == Some(0) {
+        problems.push("The forgemod contains no artifact".to_string());
+    } else if m.artifact_size > Some(MAX_ARTIFACT_SIZE) {
+        problems.push(format!(
+            "The artifact is larger than {} bytes",
+            MAX_ARTIFACT_SIZE
+        ));
+    }
+
+    if m
+        .long_description
+        .is_some_and(|d| d.chars().count() > MAX_LONG_DESCRIPTION_LEN)
+    {
+        problems.push(format!(
+            "The README must be at most {} characters",
+            MAX_LONG_DESCRIPTION_LEN
+        ));
+    }
+
+    if m
+        .release_notes
+        .is_some_and(|n| n.chars().count() > MAX_RELEASE_NOTES_LEN)
+    {
+        problems.push(format!(
+            "The release notes must be at most {} characters",
+            MAX_RELEASE_NOTES_LEN
+        ));
+    }
+
+    problems
+}
+
+/// The `422` response for an upload with `problems`.
+pub fn problems_response(problems: Vec<String>) -> HttpResponse {
+    HttpResponse::UnprocessableEntity().json(json!({ "errors": problems }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> ModManifest<'static> {
+        ModManifest {
+            slug: "song-loader_2",
+            name: "Song Loader",
+            description: "Loads custom songs",
+            website: "https://example.com/song-loader",
+            artifact_size: Some(1024),
+            release_notes: Some("Fixed everything"),
+            long_description: None,
+        }
+    }
+
+    #[test]
+    fn accepts_valid_manifest() {
+        assert!(validate_manifest(&manifest()).is_empty());
+        assert!(validate_manifest(&ModManifest {
+            website: "",
+            artifact_size: None,
+            ..manifest()
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_slugs() {
+        let long = "a".repeat(MAX_SLUG_LEN + 1);
+        for slug in ["Song-Loader", "song loader", "song.loader", "-song-loader", &long] {
+            let problems = validate_manifest(&ModManifest {
+                slug,
+                ..manifest()
+            });
+            assert_eq!(problems.len(), 1, "{}", slug);
+            assert!(problems[0].starts_with("The mod id"), "{}", slug);
+        }
+    }
+
+    #[test]
+    fn reports_every_missing_field() {
+        let problems = validate_manifest(&ModManifest {
+            slug: "",
+            name: " ",
+            description: "",
+            ..manifest()
+        });
+
+        assert_eq!(
+            problems,
+            vec![
+                "The mod id is missing",
+                "The mod name is missing",
+                "The mod description is missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_websites() {
+        for website in ["example.com", "not a url", "ftp://example.com", "javascript:alert(1)"] {
+            let problems = validate_manifest(&ModManifest {
+                website,
+                ..manifest()
+            });
+            assert_eq!(
+                problems,
+                vec![format!("The website `{}` is not a valid http(s) URL", website)]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_oversized_artifacts() {
+        let empty = validate_manifest(&ModManifest {
+            artifact_size: Some(0),
+            ..manifest()
+        });
+        assert_eq!(empty, vec!["The forgemod contains no artifact"]);
+
+        let oversized = validate_manifest(&ModManifest {
+            artifact_size: Some(MAX_ARTIFACT_SIZE + 1),
+            ..manifest()
+        });
+        assert_eq!(oversized.len(), 1);
+        assert!(oversized[0].starts_with("The artifact is larger"));
+    }
+
+    #[test]
+    fn rejects_oversized_markdown() {
+        let readme = "a".repeat(MAX_LONG_DESCRIPTION_LEN + 1);
+        let notes = "a".repeat(MAX_RELEASE_NOTES_LEN + 1);
+        let problems = validate_manifest(&ModManifest {
+            long_description: Some(&readme),
+            release_notes: Some(&notes),
+            ..manifest()
+        });
+
+        assert_eq!(problems.len(), 2);
+    }
+}
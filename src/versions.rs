@@ -1,13 +1,24 @@
 use chrono::{DateTime, Utc};
 use entity::prelude::*;
 use juniper::{
-    FieldError, FieldResult, GraphQLObject,
+    graphql_value, FieldError, FieldResult, GraphQLObject,
 };
 use serde::{Serialize, Deserialize};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, DatabaseConnection};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    IntoActiveModel, QueryFilter, Set, TransactionTrait,
+};
+use semver::Version;
 use uuid::Uuid;
 
-use crate::Database;
+use crate::{
+    analysis::AnalysisReport,
+    auth::can_edit_mod,
+    compatibility::{self, GCompatibility},
+    game_versions, jobs,
+    validation::MAX_RELEASE_NOTES_LEN,
+    Database,
+};
 
 #[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
 pub struct GVersion {
@@ -15,17 +26,41 @@ pub struct GVersion {
     pub mod_id: Uuid,
     pub version: String,
     pub approved: bool,
+    /// Whether the upload scan flagged the mod's dll.
+    pub risky: bool,
+    pub analysis: Option<AnalysisReport>,
+    /// Markdown notes on what changed in this version.
+    pub release_notes: Option<String>,
     pub download_url: String,
     pub supported_game_versions: Vec<String>,
+    /// What players report about the game versions they tried this version on.
+    pub compatibility: Vec<GCompatibility>,
     pub stats: GVersionStats,
     pub created_at: DateTime<Utc>,
 }
 
+/// The release notes of one version in a changelog.
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GChangelogEntry {
+    pub version: String,
+    pub release_notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
+pub struct GChangelog {
+    /// Newest version first.
+    pub entries: Vec<GChangelogEntry>,
+    /// The notes of every entry as one Markdown document, a heading per version.
+    pub markdown: String,
+}
+
 #[derive(GraphQLObject, Debug, Deserialize, Serialize, Clone)]
 pub struct GVersionStats {
     pub downloads: i32,
-    // pub rating: f32,
-    // pub rating_count: i32,
+    /// Average rating of reviews written about this version, 0 while it has none.
+    pub rating: f64,
+    pub rating_count: i32,
 }
 
 impl GVersion {
@@ -49,16 +84,26 @@ impl GVersion {
             .unwrap()
             .unwrap();
 
+        let compatibility = compatibility::summarize(db, v.id).await?;
+
         Ok(GVersion {
             id: Uuid::from_bytes(*v.id.as_bytes()),
             mod_id: Uuid::from_bytes(*v.mod_id.as_bytes()),
             version: v.version,
             supported_game_versions: versions,
+            compatibility,
             created_at: v.created_at.and_utc(),
             approved: v.approved,
+            risky: v.risky,
+            analysis: v
+                .analysis
+                .and_then(|a| serde_json::from_value(a).ok()),
+            release_notes: v.release_notes,
             download_url: v.download_url,
             stats: GVersionStats {
                 downloads: stats.downloads,
+                rating: stats.rating,
+                rating_count: stats.rating_count,
             },
         })
     }
@@ -79,3 +124,289 @@ pub async fn find_by_mod_id(db: &DatabaseConnection, id: Uuid) -> FieldResult<Ve
     }
     Ok(r)
 }
+
+/// Changes the game versions an uploaded version supports, so authors don't have to re-upload
+/// a mod that keeps working after a game update.
+pub async fn set_game_versions(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    version: String,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> FieldResult<GVersion> {
+    let (m, v) = find_version(db, slug, version).await?;
+
+    if !can_edit_mod(user, &m).await {
+        return Err(FieldError::new(
+            "Unauthorized",
+            graphql_value!({ "auth": "Unauthorized" }),
+        ));
+    }
+
+    let mut to_add = vec![];
+    for ver in &add {
+        let game_version = resolve_game_version(db, ver).await?;
+        if game_version.deprecated {
+            return Err(FieldError::new(
+                format!("Game version `{}` has been deprecated", ver),
+                graphql_value!({ "add": "Game version has been deprecated" }),
+            ));
+        }
+        to_add.push(game_version.id);
+    }
+
+    let mut to_remove = vec![];
+    for ver in &remove {
+        to_remove.push(resolve_game_version(db, ver).await?.id);
+    }
+
+    let mut supported = VersionBeatSaberVersions::find()
+        .filter(entity::version_beat_saber_versions::Column::VersionId.eq(v.id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|s| s.beat_saber_version_id)
+        .collect::<Vec<_>>();
+    let current = supported.clone();
+
+    supported.retain(|id| !to_remove.contains(id));
+    for id in to_add {
+        if !supported.contains(&id) {
+            supported.push(id);
+        }
+    }
+
+    if supported.is_empty() {
+        return Err(FieldError::new(
+            "A version needs at least one supported game version",
+            graphql_value!({ "remove": "A version needs at least one supported game version" }),
+        ));
+    }
+
+    let trans = db.begin().await?;
+
+    VersionBeatSaberVersions::delete_many()
+        .filter(entity::version_beat_saber_versions::Column::VersionId.eq(v.id))
+        .filter(
+            entity::version_beat_saber_versions::Column::BeatSaberVersionId
+                .is_not_in(supported.clone()),
+        )
+        .exec(&trans)
+        .await?;
+    for id in supported.iter().filter(|id| !current.contains(id)) {
+        entity::version_beat_saber_versions::ActiveModel {
+            version_id: Set(v.id),
+            beat_saber_version_id: Set(*id),
+        }
+        .insert(&trans)
+        .await?;
+    }
+
+    sync_mod_game_versions(&trans, m.id).await?;
+    jobs::enqueue(&trans, jobs::Job::IndexMod { mod_id: m.id }).await?;
+
+    trans.commit().await?;
+
+    GVersion::from_db_version(db, v).await
+}
+
+/// Replaces the release notes of an uploaded version. `None` removes them.
+pub async fn set_release_notes(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+    slug: String,
+    version: String,
+    notes: Option<String>,
+) -> FieldResult<GVersion> {
+    let (m, v) = find_version(db, slug, version).await?;
+
+    if !can_edit_mod(user, &m).await {
+        return Err(FieldError::new(
+            "Unauthorized",
+            graphql_value!({ "auth": "Unauthorized" }),
+        ));
+    }
+
+    let notes = notes
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty());
+    if notes
+        .as_ref()
+        .is_some_and(|n| n.chars().count() > MAX_RELEASE_NOTES_LEN)
+    {
+        return Err(FieldError::new(
+            format!(
+                "Release notes must be at most {} characters",
+                MAX_RELEASE_NOTES_LEN
+            ),
+            graphql_value!({ "notes": "Release notes are too long" }),
+        ));
+    }
+
+    let mut am = v.into_active_model();
+    am.release_notes = Set(notes);
+
+    GVersion::from_db_version(db, am.update(db).await?).await
+}
+
+fn parse_bound(version: Option<String>) -> FieldResult<Option<Version>> {
+    version
+        .map(|v| {
+            Version::parse(&v).map_err(|_| {
+                FieldError::new(
+                    format!("`{}` is not valid semver", v),
+                    graphql_value!({ "version": "Invalid semver" }),
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Collects the release notes of the approved versions after `from` up to and including `to`,
+/// so mod managers can show what an update brings. Either bound may be left out.
+pub async fn changelog(
+    db: &DatabaseConnection,
+    slug: String,
+    from: Option<String>,
+    to: Option<String>,
+) -> FieldResult<GChangelog> {
+    let from = parse_bound(from)?;
+    let to = parse_bound(to)?;
+    if let (Some(from), Some(to)) = (&from, &to) {
+        if from > to {
+            return Err(FieldError::new(
+                "`from` must not be newer than `to`",
+                graphql_value!({ "from": "`from` is newer than `to`" }),
+            ));
+        }
+    }
+
+    let m = Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Mod not found",
+                graphql_value!({ "internal_error": "Mod not found" }),
+            )
+        })?;
+
+    let mut versions = Versions::find()
+        .filter(entity::versions::Column::ModId.eq(m.id))
+        .filter(entity::versions::Column::Approved.eq(true))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|v| Version::parse(&v.version).ok().map(|parsed| (parsed, v)))
+        .filter(|(parsed, _)| from.as_ref().is_none_or(|from| parsed > from))
+        .filter(|(parsed, _)| to.as_ref().is_none_or(|to| parsed <= to))
+        .collect::<Vec<_>>();
+    versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let markdown = versions
+        .iter()
+        .filter_map(|(parsed, v)| {
+            v.release_notes
+                .as_ref()
+                .map(|notes| format!("## {}\n\n{}", parsed, notes))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    Ok(GChangelog {
+        entries: versions
+            .into_iter()
+            .map(|(_, v)| GChangelogEntry {
+                version: v.version,
+                release_notes: v.release_notes,
+                created_at: v.created_at.and_utc(),
+            })
+            .collect(),
+        markdown,
+    })
+}
+
+/// Looks up a version of the mod `slug`, returning both.
+pub(crate) async fn find_version(
+    db: &DatabaseConnection,
+    slug: String,
+    version: String,
+) -> FieldResult<(entity::mods::Model, entity::versions::Model)> {
+    let m = Mods::find()
+        .filter(entity::mods::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Mod not found",
+                graphql_value!({ "internal_error": "Mod not found" }),
+            )
+        })?;
+
+    let v = Versions::find()
+        .filter(entity::versions::Column::ModId.eq(m.id))
+        .filter(entity::versions::Column::Version.eq(version))
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            FieldError::new(
+                "Version not found",
+                graphql_value!({ "internal_error": "Version not found" }),
+            )
+        })?;
+
+    Ok((m, v))
+}
+
+pub(crate) async fn resolve_game_version(
+    db: &DatabaseConnection,
+    ver: &str,
+) -> FieldResult<entity::beat_saber_versions::Model> {
+    game_versions::resolve(db, ver).await?.ok_or_else(|| {
+        FieldError::new(
+            format!("Unknown game version `{}`", ver),
+            graphql_value!({ "version": "Unknown game version" }),
+        )
+    })
+}
+
+/// Rebuilds the game versions a mod supports from those of its versions.
+pub(crate) async fn sync_mod_game_versions<C: ConnectionTrait>(
+    db: &C,
+    mod_id: sea_orm::prelude::Uuid,
+) -> Result<(), DbErr> {
+    let version_ids = Versions::find()
+        .filter(entity::versions::Column::ModId.eq(mod_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|v| v.id)
+        .collect::<Vec<_>>();
+
+    let mut supported = VersionBeatSaberVersions::find()
+        .filter(entity::version_beat_saber_versions::Column::VersionId.is_in(version_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|s| s.beat_saber_version_id)
+        .collect::<Vec<_>>();
+    supported.sort();
+    supported.dedup();
+
+    ModBeatSaberVersions::delete_many()
+        .filter(entity::mod_beat_saber_versions::Column::ModId.eq(mod_id))
+        .exec(db)
+        .await?;
+    for id in supported {
+        entity::mod_beat_saber_versions::ActiveModel {
+            mod_id: Set(mod_id),
+            beat_saber_version_id: Set(id),
+        }
+        .insert(db)
+        .await?;
+    }
+
+    Ok(())
+}